@@ -12,6 +12,7 @@ struct SimParams {
 
 struct VertexOutput {
     [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
 };
 
 [[stage(vertex)]]
@@ -20,6 +21,8 @@ fn main(
 ) -> VertexOutput {
     var out: VertexOutput;
     out.position = vec4<f32>(position, 0.0, 1.0);
+    // for the stretched entry points, the trail texture covers the viewport, first row at the top
+    out.uv = vec2<f32>(position.x * 0.5 + 0.5, 0.5 - position.y * 0.5);
     return out;
 }
 
@@ -54,11 +57,23 @@ fn hsv2rgb(c: vec3<f32>) -> vec3<f32>
 [[group(0), binding(1)]] var r_color: texture_2d<f32>;
 [[group(0), binding(2)]] var r_sampler: sampler;
 
-[[stage(fragment)]]
-fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
-    var uv: vec2<f32> = vec2<f32>(in.position.x / 1600.0 / 2.0, in.position.y / 900.0 / 2.0);
+// the trail texture is R32Float, which can't be filtered by a sampler, so
+// the stretched entry points blend the four nearest texels themselves
+fn sample_bilinear(uv: vec2<f32>) -> f32 {
+    var size: vec2<i32> = vec2<i32>(3200, 1800);
+    var texel: vec2<f32> = uv * vec2<f32>(size) - vec2<f32>(0.5, 0.5);
+    var base: vec2<f32> = floor(texel);
+    var f: vec2<f32> = texel - base;
+    var last: vec2<i32> = size - vec2<i32>(1, 1);
+    var i0: vec2<i32> = min(max(vec2<i32>(base), vec2<i32>(0, 0)), last);
+    var i1: vec2<i32> = min(max(vec2<i32>(base) + vec2<i32>(1, 1), vec2<i32>(0, 0)), last);
+    var top: f32 = mix(textureLoad(r_color, i0, 0).r, textureLoad(r_color, vec2<i32>(i1.x, i0.y), 0).r, f.x);
+    var bottom: f32 = mix(textureLoad(r_color, vec2<i32>(i0.x, i1.y), 0).r, textureLoad(r_color, i1, 0).r, f.x);
+    return mix(top, bottom, f.y);
+}
 
-    var weight: f32 = textureSample(r_color, r_sampler, uv).r / 8.0;
+fn shade(trail: f32) -> vec3<f32> {
+    var weight: f32 = trail / 8.0;
     weight = pow(weight, params.color_pow);
     
     var color: vec3<f32>;
@@ -78,6 +93,24 @@ fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
         color = hsv2rgb(hsv);
     }
 
-    return vec4<f32>(color, 1.0);
+    return color;
+}
+
+// one texel per pixel from the top left, the way Simulation::draw shows the trails
+[[stage(fragment)]]
+fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    var uv: vec2<f32> = vec2<f32>(in.position.x / 1600.0 / 2.0, in.position.y / 900.0 / 2.0);
+    return vec4<f32>(shade(textureSample(r_color, r_sampler, uv).r), 1.0);
+}
+
+[[stage(fragment)]]
+fn main_stretched(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return vec4<f32>(shade(sample_bilinear(in.uv)), 1.0);
+}
 
+// premultiplied alpha from the brightness, so over black it looks the same as main_stretched
+[[stage(fragment)]]
+fn main_transparent(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    var color: vec3<f32> = shade(sample_bilinear(in.uv));
+    return vec4<f32>(color, clamp(max(color.r, max(color.g, color.b)), 0.0, 1.0));
 }
@@ -0,0 +1,342 @@
+use std::io::{ErrorKind, Read};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use serde_json::json;
+use tiny_http::{Header, Method, Response, StatusCode};
+use tungstenite::Message;
+use crate::params::Params;
+use crate::remote::Command;
+
+/// How long a request waits for the render loop to answer
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a WebSocket client may take to finish its handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often the WebSocket thread looks for new clients and incoming frames
+/// while no events arrive
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long writes to a WebSocket client may keep failing to get through
+/// before it counts as stuck and is dropped
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Events queued for a WebSocket client whose writes don't get through, later ones are dropped
+const SEND_QUEUE: usize = 256;
+
+const INDEX: &str = "\
+GET  /params           current preset as JSON
+PUT  /params           replace the values of the current preset
+GET  /presets          preset names and the selected index
+POST /presets/select   {\"name\": ...} or {\"index\": ...}
+POST /pause, /resume, /step, /reset
+GET  /screenshot       PNG of the current frame
+
+A WebSocket on the next port streams params and frame stats.
+";
+
+/// Something the render loop has to answer
+#[derive(Debug)]
+pub enum Call {
+    GetParams,
+    PutParams(Params),
+    Presets,
+    Command(Command),
+    Screenshot,
+}
+
+#[derive(Debug)]
+pub enum Reply {
+    Json(serde_json::Value),
+    Png(Vec<u8>),
+    Error(u16, String),
+}
+
+pub struct Request {
+    pub call: Call,
+    reply: Sender<Reply>,
+}
+
+impl Request {
+    pub fn reply(self, reply: Reply) {
+        // the client may have timed out already
+        let _ = self.reply.send(reply);
+    }
+}
+
+type Socket = tungstenite::WebSocket<TcpStream>;
+
+struct Client {
+    socket: Socket,
+    /// Set while writes would block
+    blocked_since: Option<Instant>,
+}
+
+/// Serves the remote control API on background threads: HTTP requests on
+/// `addr`, each on its own thread, and the WebSocket event stream on the next port
+pub struct ApiServer {
+    addr: SocketAddr,
+    ws_addr: SocketAddr,
+    requests: Receiver<Request>,
+    events: Sender<String>,
+    listeners: Arc<AtomicUsize>,
+    stop: Arc<AtomicBool>,
+}
+
+impl ApiServer {
+    /// Binds `addr` and the port after it, port 0 picks two free ports
+    pub fn bind(addr: impl ToSocketAddrs) -> std::io::Result<ApiServer> {
+        let addr = addr.to_socket_addrs()?.next()
+            .ok_or_else(|| std::io::Error::new(ErrorKind::InvalidInput, "no address to bind"))?;
+        let ws_port = match addr.port() {
+            0 => 0,
+            port => port.checked_add(1)
+                .ok_or_else(|| std::io::Error::new(ErrorKind::InvalidInput, "no port left for the WebSocket"))?,
+        };
+        let server = tiny_http::Server::http(addr)
+            .map_err(|e| std::io::Error::new(ErrorKind::Other, e.to_string()))?;
+        let addr = server.server_addr();
+        let ws_listener = TcpListener::bind((addr.ip(), ws_port))?;
+        ws_listener.set_nonblocking(true)?;
+        let ws_addr = ws_listener.local_addr()?;
+        let (sender, requests) = mpsc::channel();
+        let (events, queued) = mpsc::channel();
+        let listeners = Arc::new(AtomicUsize::new(0));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let stopped = stop.clone();
+        std::thread::Builder::new().name("api".to_owned()).spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                match server.recv_timeout(Duration::from_millis(200)) {
+                    Ok(Some(request)) => {
+                        // a request waits for the render loop, so one slow client can't hold up the others
+                        let sender = sender.clone();
+                        let spawned = std::thread::Builder::new()
+                            .name("api-request".to_owned())
+                            .spawn(move || handle(request, &sender));
+                        if let Err(e) = spawned {
+                            log::warn!("API: {}", e);
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        log::error!("API: {}", e);
+                        return;
+                    }
+                }
+            }
+        })?;
+
+        let (stopped, counted) = (stop.clone(), listeners.clone());
+        std::thread::Builder::new().name("api-ws".to_owned()).spawn(move || {
+            stream_events(ws_listener, queued, &counted, &stopped);
+        })?;
+
+        log::info!("API listening on http://{}, WebSocket on ws://{}", addr, ws_addr);
+        Ok(ApiServer { addr, ws_addr, requests, events, listeners, stop })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Where the WebSocket event stream is served
+    pub fn ws_addr(&self) -> SocketAddr {
+        self.ws_addr
+    }
+
+    /// Requests received since the last call
+    pub fn poll(&self) -> impl Iterator<Item = Request> + '_ {
+        self.requests.try_iter()
+    }
+
+    pub fn has_listeners(&self) -> bool {
+        self.listeners.load(Ordering::Relaxed) > 0
+    }
+
+    /// Queues an event for every WebSocket client, the WebSocket thread writes it
+    pub fn broadcast(&self, event: &serde_json::Value) {
+        // fails only when the WebSocket thread has stopped
+        let _ = self.events.send(event.to_string());
+    }
+}
+
+impl Drop for ApiServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+fn handle(mut request: tiny_http::Request, sender: &Sender<Request>) {
+    let method = request.method().clone();
+    let url = request.url().split('?').next().unwrap_or_default().to_owned();
+
+    let mut body = String::new();
+    if let Err(e) = request.as_reader().read_to_string(&mut body) {
+        respond(request, Reply::Error(400, e.to_string()));
+        return;
+    }
+
+    let call = match (&method, url.as_str()) {
+        (Method::Get, "/") => {
+            let _ = request.respond(Response::from_string(INDEX));
+            return;
+        }
+        (Method::Get, "/params") => Ok(Call::GetParams),
+        (Method::Put, "/params") => serde_json::from_str(&body).map(Call::PutParams).map_err(|e| e.to_string()),
+        (Method::Get, "/presets") => Ok(Call::Presets),
+        (Method::Post, "/presets/select") => select(&body).map(Call::Command),
+        (Method::Post, "/pause") => Ok(Call::Command(Command::Pause(true))),
+        (Method::Post, "/resume") => Ok(Call::Command(Command::Pause(false))),
+        (Method::Post, "/step") => Ok(Call::Command(Command::Step)),
+        (Method::Post, "/reset") => Ok(Call::Command(Command::Reset)),
+        (Method::Get, "/screenshot") => Ok(Call::Screenshot),
+        _ => {
+            respond(request, Reply::Error(404, format!("no route for {} {}", method, url)));
+            return;
+        }
+    };
+    let call = match call {
+        Ok(call) => call,
+        Err(e) => {
+            respond(request, Reply::Error(400, e));
+            return;
+        }
+    };
+
+    let (reply, answer) = mpsc::channel();
+    if sender.send(Request { call, reply }).is_err() {
+        respond(request, Reply::Error(503, "shutting down".to_owned()));
+        return;
+    }
+    let reply = answer
+        .recv_timeout(REPLY_TIMEOUT)
+        .unwrap_or_else(|_| Reply::Error(504, "the render loop did not answer".to_owned()));
+    respond(request, reply);
+}
+
+/// Parses `{"name": ...}` or `{"index": ...}`
+fn select(body: &str) -> Result<Command, String> {
+    let value: serde_json::Value = serde_json::from_str(body).map_err(|e| e.to_string())?;
+    if let Some(name) = value.get("name").and_then(|n| n.as_str()) {
+        return Ok(Command::Preset(name.to_owned()));
+    }
+    if let Some(index) = value.get("index").and_then(|i| i.as_u64()) {
+        return Ok(Command::PresetIndex(index as usize));
+    }
+    Err("expected {\"name\": ...} or {\"index\": ...}".to_owned())
+}
+
+fn respond(request: tiny_http::Request, reply: Reply) {
+    let header = |value: &str| Header::from_bytes(&b"Content-Type"[..], value.as_bytes()).unwrap();
+    let result = match reply {
+        Reply::Json(value) => request.respond(
+            Response::from_string(value.to_string()).with_header(header("application/json")),
+        ),
+        Reply::Png(data) => request.respond(Response::from_data(data).with_header(header("image/png"))),
+        Reply::Error(code, message) => request.respond(
+            Response::from_string(json!({ "error": message }).to_string())
+                .with_status_code(StatusCode(code))
+                .with_header(header("application/json")),
+        ),
+    };
+    if let Err(e) = result {
+        log::warn!("API: {}", e);
+    }
+}
+
+/// Accepts WebSocket clients and writes the queued events to them until the
+/// server is dropped. The sockets are non-blocking, a client that stops reading
+/// is dropped after `WRITE_TIMEOUT`
+fn stream_events(listener: TcpListener, events: Receiver<String>, listeners: &AtomicUsize, stop: &AtomicBool) {
+    let (opened, handshaken) = mpsc::channel();
+    let mut clients: Vec<Client> = Vec::new();
+    while !stop.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let opened = opened.clone();
+                let spawned = std::thread::Builder::new().name("api-handshake".to_owned()).spawn(move || {
+                    match handshake(stream) {
+                        Ok(socket) => {
+                            let _ = opened.send(socket);
+                        }
+                        Err(e) => log::warn!("API: {}", e),
+                    }
+                });
+                if let Err(e) = spawned {
+                    log::warn!("API: {}", e);
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(e) => log::warn!("API: {}", e),
+        }
+        clients.extend(handshaken.try_iter().map(|socket| Client { socket, blocked_since: None }));
+
+        let pending: Vec<String> = match events.recv_timeout(POLL_INTERVAL) {
+            Ok(event) => std::iter::once(event).chain(events.try_iter()).collect(),
+            Err(RecvTimeoutError::Timeout) => Vec::new(),
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+        clients = clients
+            .into_iter()
+            .filter_map(|mut client| if exchange(&mut client, &pending) { Some(client) } else { None })
+            .collect();
+        listeners.store(clients.len(), Ordering::Relaxed);
+    }
+}
+
+fn handshake(stream: TcpStream) -> Result<Socket, String> {
+    // some platforms hand out sockets that are non-blocking like the listener
+    stream.set_nonblocking(false).map_err(|e| e.to_string())?;
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT)).map_err(|e| e.to_string())?;
+    stream.set_write_timeout(Some(HANDSHAKE_TIMEOUT)).map_err(|e| e.to_string())?;
+    let config = tungstenite::protocol::WebSocketConfig {
+        max_send_queue: Some(SEND_QUEUE),
+        ..Default::default()
+    };
+    let socket = tungstenite::accept_with_config(stream, Some(config)).map_err(|e| e.to_string())?;
+    socket.get_ref().set_nonblocking(true).map_err(|e| e.to_string())?;
+    Ok(socket)
+}
+
+/// Reads what the client sent, which answers pings and closes, then writes
+/// `events`. Returns false once the client is gone
+fn exchange(client: &mut Client, events: &[String]) -> bool {
+    let socket = &mut client.socket;
+    loop {
+        match socket.read_message() {
+            // clients have nothing to say besides pings and closes
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => break,
+            Err(_) => return false,
+        }
+    }
+    // write_pending flushes what an earlier call left queued
+    let mut blocked = false;
+    let written = std::iter::once(socket.write_pending())
+        .chain(events.iter().map(|event| socket.write_message(Message::Text(event.clone()))))
+        .all(|result| match result {
+            Ok(()) => true,
+            Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => {
+                blocked = true;
+                true
+            }
+            Err(_) => false,
+        });
+    if !written {
+        return false;
+    }
+    if !blocked {
+        client.blocked_since = None;
+        return true;
+    }
+    let since = *client.blocked_since.get_or_insert_with(Instant::now);
+    if since.elapsed() < WRITE_TIMEOUT {
+        return true;
+    }
+    log::warn!("API: dropping a WebSocket client that stopped reading");
+    false
+}
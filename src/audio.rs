@@ -0,0 +1,127 @@
+use std::path::{Path, PathBuf};
+use crate::modulation::Features;
+
+/// Band edges in Hz, bass below the first, treble above the second
+const BASS_CUTOFF: f32 = 250.0;
+const TREBLE_CUTOFF: f32 = 4000.0;
+/// Seconds of history an onset is measured against
+const ONSET_WINDOW: f32 = 0.25;
+
+/// A WAV file analyzed once per simulation frame
+pub struct AudioTrack {
+    pub path: PathBuf,
+    /// Features of every frame of `frame_time` seconds
+    pub frames: Vec<Features>,
+    pub frame_time: f32,
+}
+
+impl AudioTrack {
+    /// Decodes `path` and analyzes it in steps of `frame_time` seconds
+    pub fn load(path: &Path, frame_time: f32) -> Result<AudioTrack, String> {
+        let (samples, sample_rate) = read_mono(path)?;
+        Ok(AudioTrack {
+            path: path.to_owned(),
+            frames: analyze(&samples, sample_rate, frame_time),
+            frame_time,
+        })
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.frames.len() as f32 * self.frame_time
+    }
+
+    pub fn features(&self, frame: usize) -> Option<&Features> {
+        self.frames.get(frame)
+    }
+}
+
+/// Samples mixed down to mono in [-1, 1], and the sample rate
+fn read_mono(path: &Path) -> Result<(Vec<f32>, u32), String> {
+    let error = |e: hound::Error| format!("Error reading {}: {}", path.display(), e);
+    let mut reader = hound::WavReader::open(path).map_err(error)?;
+    let spec = reader.spec();
+    let interleaved: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>().map_err(error)?,
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+            reader.samples::<i32>()
+                .map(|s| s.map(|s| s as f32 * scale))
+                .collect::<Result<_, _>>()
+                .map_err(error)?
+        }
+    };
+    let channels = spec.channels.max(1) as usize;
+    let mono = interleaved
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect();
+    Ok((mono, spec.sample_rate))
+}
+
+/// Coefficient of a one-pole low-pass filter
+fn one_pole(cutoff: f32, sample_rate: u32) -> f32 {
+    1.0 - (-2.0 * std::f32::consts::PI * cutoff / sample_rate as f32).exp()
+}
+
+/// Band energies from one-pole splits, onsets from the rise of the RMS over its recent average
+fn analyze(samples: &[f32], sample_rate: u32, frame_time: f32) -> Vec<Features> {
+    let samples_per_frame = ((sample_rate as f32 * frame_time).round() as usize).max(1);
+    let (bass_k, treble_k) = (one_pole(BASS_CUTOFF, sample_rate), one_pole(TREBLE_CUTOFF, sample_rate));
+    let (mut low, mut high) = (0.0f32, 0.0f32);
+
+    let mut frames: Vec<Features> = samples
+        .chunks(samples_per_frame)
+        .map(|chunk| {
+            let mut sums = [0.0f32; 4];
+            for &x in chunk {
+                low += bass_k * (x - low);
+                high += treble_k * (x - high);
+                let (bass, mid, treble) = (low, high - low, x - high);
+                sums[0] += x * x;
+                sums[1] += bass * bass;
+                sums[2] += mid * mid;
+                sums[3] += treble * treble;
+            }
+            let rms = |sum: f32| (sum / chunk.len() as f32).sqrt();
+            Features {
+                rms: rms(sums[0]),
+                bass: rms(sums[1]),
+                mid: rms(sums[2]),
+                treble: rms(sums[3]),
+                onset: 0.0,
+            }
+        })
+        .collect();
+
+    let window = ((ONSET_WINDOW / frame_time) as usize).max(1);
+    for i in 0..frames.len() {
+        let history = &frames[i.saturating_sub(window)..i];
+        if history.is_empty() {
+            continue;
+        }
+        let average = history.iter().map(|f| f.rms).sum::<f32>() / history.len() as f32;
+        frames[i].onset = (frames[i].rms - average).max(0.0);
+    }
+
+    normalize(&mut frames);
+    frames
+}
+
+/// Scales each feature so its loudest frame is 1
+fn normalize(frames: &mut [Features]) {
+    let peak = |get: fn(&Features) -> f32| frames.iter().map(get).fold(0.0f32, f32::max).max(1e-6);
+    let (rms, bass, mid, treble, onset) = (
+        peak(|f| f.rms),
+        peak(|f| f.bass),
+        peak(|f| f.mid),
+        peak(|f| f.treble),
+        peak(|f| f.onset),
+    );
+    for f in frames.iter_mut() {
+        f.rms /= rms;
+        f.bass /= bass;
+        f.mid /= mid;
+        f.treble /= treble;
+        f.onset /= onset;
+    }
+}
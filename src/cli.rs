@@ -0,0 +1,171 @@
+use std::path::PathBuf;
+use moldsim::simulation::MAX_PARTICLES;
+
+pub const USAGE: &str = "\
+usage: wgpu-toy [options]
+
+options:
+    --preset NAME          start with the preset called NAME
+    --params FILE          load and save user presets from FILE
+    --config FILE          read application settings from FILE instead of Config.toml
+    --resolution WxH       window size in pixels, e.g. 1920x1080
+    --particles N          number of simulated particles, below 8388608
+    --backend BACKEND      vulkan, metal, dx12, dx11, gl or webgpu
+    --power PREFERENCE     GPU power preference, low or high
+    --fullscreen           start in borderless fullscreen, F11 cycles the window modes
+    --monitor NAME         monitor used for fullscreen
+    --seed N               seed for the initial particle positions
+    --no-ui                hide the configuration window and cursor, Tab shows them
+    --playlist NAMES       cycle through comma separated presets, e.g. \"Default,Disperse\"
+    --interval SECONDS     how long each playlist preset is shown (default 60)
+    --transition SECONDS   morph time between presets
+    --reset                respawn particles when the playlist switches presets
+    --play                 play the playlist saved with the user presets
+    --animate              play the keyframe timeline saved with the user presets
+    --audio FILE           modulate parameters with the audio mappings from a WAV file
+    --script FILE          automate parameters with the on_frame hook of a Rhai script
+    --midi PORT            connect to the MIDI input whose name contains PORT
+    --midi-replay FILE     feed recorded MIDI control changes from FILE through the bindings
+    --osc PORT             listen for OSC messages on PORT
+    --api PORT             serve the HTTP and WebSocket API on localhost:PORT
+    --render DIR           render frames offscreen to DIR as PNG files instead of opening a window
+    --frames N             number of frames to render, defaults to one pass of the playlist
+    --list-presets         print the available presets and exit
+    -h, --help             print this message and exit
+
+WGPU_BACKEND and WGPU_POWER_PREF are used when --backend and --power are not given.";
+
+#[derive(Debug, Default)]
+pub struct Args {
+    pub preset: Option<String>,
+    pub params: Option<PathBuf>,
+    pub config: Option<PathBuf>,
+    pub resolution: Option<(u32, u32)>,
+    pub particles: Option<u32>,
+    pub backend: Option<wgpu::BackendBit>,
+    pub power: Option<wgpu::PowerPreference>,
+    pub fullscreen: bool,
+    pub monitor: Option<String>,
+    pub seed: Option<u64>,
+    pub no_ui: bool,
+    pub playlist: Vec<String>,
+    pub interval: Option<f32>,
+    pub transition: Option<f32>,
+    pub reset: bool,
+    pub play: bool,
+    pub animate: bool,
+    pub audio: Option<PathBuf>,
+    pub script: Option<PathBuf>,
+    pub midi: Option<String>,
+    pub midi_replay: Option<PathBuf>,
+    pub osc: Option<u16>,
+    pub api: Option<u16>,
+    pub render: Option<PathBuf>,
+    pub frames: Option<u32>,
+    pub list_presets: bool,
+    pub help: bool,
+}
+
+impl Args {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next().ok_or_else(|| format!("{} expects a value", name))
+            };
+            match arg.as_str() {
+                "--preset" => parsed.preset = Some(value("--preset")?),
+                "--params" => parsed.params = Some(value("--params")?.into()),
+                "--config" => parsed.config = Some(value("--config")?.into()),
+                "--resolution" => parsed.resolution = Some(parse_resolution(&value("--resolution")?)?),
+                "--particles" => parsed.particles = Some(parse_particles(&value("--particles")?)?),
+                "--backend" => parsed.backend = Some(parse_backend(&value("--backend")?)?),
+                "--power" => parsed.power = Some(parse_power(&value("--power")?)?),
+                "--fullscreen" => parsed.fullscreen = true,
+                "--monitor" => parsed.monitor = Some(value("--monitor")?),
+                "--seed" => parsed.seed = Some(parse_number("--seed", &value("--seed")?)?),
+                "--no-ui" => parsed.no_ui = true,
+                "--playlist" => {
+                    parsed.playlist = value("--playlist")?
+                        .split(',')
+                        .map(|name| name.trim().to_owned())
+                        .filter(|name| !name.is_empty())
+                        .collect();
+                }
+                "--interval" => parsed.interval = Some(parse_number("--interval", &value("--interval")?)?),
+                "--transition" => parsed.transition = Some(parse_number("--transition", &value("--transition")?)?),
+                "--reset" => parsed.reset = true,
+                "--play" => parsed.play = true,
+                "--animate" => parsed.animate = true,
+                "--audio" => parsed.audio = Some(value("--audio")?.into()),
+                "--script" => parsed.script = Some(value("--script")?.into()),
+                "--midi" => parsed.midi = Some(value("--midi")?),
+                "--midi-replay" => parsed.midi_replay = Some(value("--midi-replay")?.into()),
+                "--osc" => parsed.osc = Some(parse_number("--osc", &value("--osc")?)?),
+                "--api" => parsed.api = Some(parse_number("--api", &value("--api")?)?),
+                "--render" => parsed.render = Some(value("--render")?.into()),
+                "--frames" => parsed.frames = Some(parse_number("--frames", &value("--frames")?)?),
+                "--list-presets" => parsed.list_presets = true,
+                "-h" | "--help" => parsed.help = true,
+                other => return Err(format!("unknown argument: {}", other)),
+            }
+        }
+
+        if parsed.backend.is_none() {
+            if let Ok(backend) = std::env::var("WGPU_BACKEND") {
+                parsed.backend = Some(parse_backend(&backend).map_err(|e| format!("WGPU_BACKEND: {}", e))?);
+            }
+        }
+        if parsed.power.is_none() {
+            if let Ok(power) = std::env::var("WGPU_POWER_PREF") {
+                parsed.power = Some(parse_power(&power).map_err(|e| format!("WGPU_POWER_PREF: {}", e))?);
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("{} expects a number, got \"{}\"", name, value))
+}
+
+/// The particle buffers hold one more than the count, up to `MAX_PARTICLES`
+fn parse_particles(value: &str) -> Result<u32, String> {
+    let particles: u32 = parse_number("--particles", value)?;
+    if particles >= MAX_PARTICLES {
+        return Err(format!("--particles must be below {}, got {}", MAX_PARTICLES, particles));
+    }
+    Ok(particles)
+}
+
+pub fn parse_resolution(value: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("invalid resolution \"{}\", expected WxH like 1920x1080", value);
+    let mut parts = value.splitn(2, |c| c == 'x' || c == 'X');
+    let width: u32 = parts.next().and_then(|w| w.trim().parse().ok()).ok_or_else(invalid)?;
+    let height: u32 = parts.next().and_then(|h| h.trim().parse().ok()).ok_or_else(invalid)?;
+    if width == 0 || height == 0 {
+        return Err(invalid());
+    }
+    Ok((width, height))
+}
+
+pub fn parse_backend(value: &str) -> Result<wgpu::BackendBit, String> {
+    match value.to_lowercase().as_str() {
+        "vulkan" => Ok(wgpu::BackendBit::VULKAN),
+        "metal" => Ok(wgpu::BackendBit::METAL),
+        "dx12" => Ok(wgpu::BackendBit::DX12),
+        "dx11" => Ok(wgpu::BackendBit::DX11),
+        "gl" => Ok(wgpu::BackendBit::GL),
+        "webgpu" => Ok(wgpu::BackendBit::BROWSER_WEBGPU),
+        other => Err(format!("unknown backend \"{}\", expected vulkan, metal, dx12, dx11, gl or webgpu", other)),
+    }
+}
+
+pub fn parse_power(value: &str) -> Result<wgpu::PowerPreference, String> {
+    match value.to_lowercase().as_str() {
+        "low" => Ok(wgpu::PowerPreference::LowPower),
+        "high" => Ok(wgpu::PowerPreference::HighPerformance),
+        other => Err(format!("unknown power preference \"{}\", expected low or high", other)),
+    }
+}
@@ -0,0 +1,281 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use crate::framework::{Display, WindowMode};
+use crate::midi::MidiBinding;
+
+/// Application settings read from `Config.toml`, command line flags take precedence
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct AppConfig {
+    /// env_logger filter, `RUST_LOG` overrides it
+    pub log_level: String,
+    /// Preset selected at startup, by name
+    pub default_preset: Option<String>,
+    pub window: WindowConfig,
+    pub display: DisplayConfig,
+    pub ui: UiConfig,
+    pub osc: OscConfig,
+    pub api: ApiConfig,
+    pub midi: MidiConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct WindowConfig {
+    /// Logical size of the window
+    pub width: u32,
+    pub height: u32,
+    pub mode: WindowMode,
+    /// Monitor name used for fullscreen, the names are logged at startup
+    pub monitor: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct DisplayConfig {
+    pub present_mode: PresentMode,
+    /// Frames per second, 0 for unlimited
+    pub frame_cap: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct UiConfig {
+    pub font: PathBuf,
+    pub scale: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct OscConfig {
+    pub enabled: bool,
+    /// Interface to listen on, 0.0.0.0 accepts messages from other machines
+    pub address: String,
+    pub port: u16,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ApiConfig {
+    pub enabled: bool,
+    /// Interface to listen on, the API has no authentication so keep it local
+    pub address: String,
+    pub port: u16,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct MidiConfig {
+    /// Connect to a MIDI input at startup
+    pub enabled: bool,
+    /// Part of the input port name, the first port is used if unset
+    pub port: Option<String>,
+    /// Controllers bound to parameters, added in learn mode
+    pub bindings: Vec<MidiBinding>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PresentMode {
+    /// Vsync
+    Fifo,
+    Mailbox,
+    Immediate,
+}
+
+impl PresentMode {
+    pub fn to_wgpu(self) -> wgpu::PresentMode {
+        match self {
+            PresentMode::Fifo => wgpu::PresentMode::Fifo,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+        }
+    }
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            log_level: if cfg!(debug_assertions) { "info" } else { "warn" }.to_owned(),
+            default_preset: None,
+            window: WindowConfig::default(),
+            display: DisplayConfig::default(),
+            ui: UiConfig::default(),
+            osc: OscConfig::default(),
+            api: ApiConfig::default(),
+            midi: MidiConfig::default(),
+        }
+    }
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            width: 1600,
+            height: 900,
+            mode: WindowMode::Windowed,
+            monitor: None,
+        }
+    }
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            present_mode: PresentMode::Mailbox,
+            frame_cap: 144,
+        }
+    }
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            font: PathBuf::from("./resources/fonts/Roboto-Light.ttf"),
+            scale: 1.0,
+        }
+    }
+}
+
+impl Default for OscConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: "127.0.0.1".to_owned(),
+            port: 9000,
+        }
+    }
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: "127.0.0.1".to_owned(),
+            port: 8080,
+        }
+    }
+}
+
+/// Choices made while the app runs, kept apart from the hand-edited `Config.toml`
+/// and applied over it at startup
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct State {
+    pub window: WindowState,
+    pub midi: MidiState,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct WindowState {
+    /// Picked with F11 or in the Display panel
+    pub mode: Option<WindowMode>,
+    /// Picked in the Display panel, empty for the monitor the window is on
+    pub monitor: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct MidiState {
+    /// Last input connected in the MIDI panel
+    pub port: Option<String>,
+    /// Every binding once one is learned or removed, replacing the ones in the config
+    pub bindings: Option<Vec<MidiBinding>>,
+}
+
+/// `Config.toml` in the working directory if there is one, otherwise the per-user config
+pub fn default_config_path() -> PathBuf {
+    let local = PathBuf::from("Config.toml");
+    if local.exists() {
+        return local;
+    }
+    dirs::config_dir()
+        .map(|dir| dir.join("moldsim").join("Config.toml"))
+        .unwrap_or(local)
+}
+
+/// `moldsim/state.toml` in the per-user config directory
+pub fn default_state_path() -> PathBuf {
+    dirs::config_dir()
+        .map(|dir| dir.join("moldsim").join("state.toml"))
+        .unwrap_or_else(|| PathBuf::from("state.toml"))
+}
+
+fn read_toml<T: Default + serde::de::DeserializeOwned>(path: &Path) -> Result<T, String> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    let contents = std::fs::read_to_string(path).map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+    toml::from_str(&contents).map_err(|e| format!("Error parsing {}: {}", path.display(), e))
+}
+
+impl AppConfig {
+    /// Reads the config at `path`, a missing file gives the defaults
+    pub fn load(path: &Path) -> Result<AppConfig, String> {
+        read_toml(path)
+    }
+
+    /// Applies the choices saved in `state` over the file's values
+    pub fn apply_state(&mut self, state: &State) {
+        if let Some(mode) = state.window.mode {
+            self.window.mode = mode;
+        }
+        if let Some(monitor) = &state.window.monitor {
+            self.window.monitor = Some(monitor.clone()).filter(|name| !name.is_empty());
+        }
+        if let Some(port) = &state.midi.port {
+            self.midi.port = Some(port.clone());
+        }
+        if let Some(bindings) = &state.midi.bindings {
+            self.midi.bindings = bindings.clone();
+        }
+    }
+
+    pub fn frame_cap(&self) -> Option<u32> {
+        match self.display.frame_cap {
+            0 => None,
+            fps => Some(fps),
+        }
+    }
+
+    pub fn to_display(&self) -> Display {
+        Display {
+            present_mode: self.display.present_mode.to_wgpu(),
+            frame_cap: self.frame_cap(),
+            window_mode: self.window.mode,
+            monitor: self.window.monitor.clone(),
+        }
+    }
+}
+
+impl State {
+    /// Reads the state at `path`, a missing file gives no overrides
+    pub fn load(path: &Path) -> Result<State, String> {
+        read_toml(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Error creating {}: {}", dir.display(), e))?;
+        }
+        let contents = toml::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, contents).map_err(|e| format!("Error writing {}: {}", path.display(), e))
+    }
+
+    /// Keeps the window mode and monitor if they changed from `previous`, the
+    /// rest of the display settings only come from the config. Returns true if
+    /// anything needs saving
+    pub fn update_window(&mut self, display: &Display, previous: &Display) -> bool {
+        let mut changed = false;
+        if display.window_mode != previous.window_mode {
+            self.window.mode = Some(display.window_mode);
+            changed = true;
+        }
+        if display.monitor != previous.monitor {
+            self.window.monitor = Some(display.monitor.clone().unwrap_or_default());
+            changed = true;
+        }
+        changed
+    }
+}
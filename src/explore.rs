@@ -0,0 +1,78 @@
+use rand::Rng;
+use crate::params::{ParamKind, ParamValue, Params};
+
+/// Inclusive range of a reflected field, colors are ranged per component
+fn range(kind: ParamKind, max_particles: u32) -> (f32, f32) {
+    match kind {
+        ParamKind::Float { min, max } => (min, max),
+        ParamKind::Count => (0.0, (max_particles - 1) as f32),
+        ParamKind::Color => (0.0, 1.0),
+    }
+}
+
+/// Samples every parameter uniformly within its valid range
+pub fn randomize<R: Rng>(params: &mut Params, max_particles: u32, rng: &mut R) {
+    for section in params.sections_mut().iter_mut() {
+        for (index, field) in section.fields().iter().enumerate() {
+            let (min, max) = range(field.kind, max_particles);
+            let value = match section.get(index) {
+                ParamValue::Float(_) => ParamValue::Float(rng.gen_range(min..=max)),
+                ParamValue::Count(_) => ParamValue::Count(rng.gen_range(min..=max) as u32),
+                ParamValue::Color(_) => ParamValue::Color([
+                    rng.gen_range(min..=max),
+                    rng.gen_range(min..=max),
+                    rng.gen_range(min..=max),
+                ]),
+            };
+            section.set(index, value);
+        }
+    }
+}
+
+/// Perturbs every parameter by up to `amount` of its range
+pub fn mutate<R: Rng>(params: &mut Params, amount: f32, max_particles: u32, rng: &mut R) {
+    for section in params.sections_mut().iter_mut() {
+        for (index, field) in section.fields().iter().enumerate() {
+            let (min, max) = range(field.kind, max_particles);
+            let mut nudge = |v: f32| {
+                let offset = rng.gen_range(-1.0..=1.0) * amount * (max - min);
+                (v + offset).max(min).min(max)
+            };
+            let value = match section.get(index) {
+                ParamValue::Float(v) => ParamValue::Float(nudge(v)),
+                ParamValue::Count(v) => ParamValue::Count(nudge(v as f32).round() as u32),
+                ParamValue::Color(c) => ParamValue::Color([nudge(c[0]), nudge(c[1]), nudge(c[2])]),
+            };
+            section.set(index, value);
+        }
+    }
+}
+
+/// Variants produced while exploring, oldest first
+pub struct Explorer {
+    pub amount: f32,
+    pub history: Vec<Params>,
+    pub selected: Option<usize>,
+}
+
+impl Explorer {
+    pub fn new() -> Self {
+        Self {
+            amount: 0.1,
+            history: Vec::new(),
+            selected: None,
+        }
+    }
+
+    /// Records a variant, keeping the values it was derived from as the first entry
+    pub fn push(&mut self, origin: &Params, mut variant: Params) {
+        if self.history.is_empty() {
+            let mut origin = origin.clone();
+            origin.name = format!("{} (original)", origin.name);
+            self.history.push(origin);
+        }
+        variant.name = format!("Variant {}", self.history.len());
+        self.history.push(variant);
+        self.selected = Some(self.history.len() - 1);
+    }
+}
@@ -1,7 +1,7 @@
 use std::future::Future;
 #[cfg(not(target_arch = "wasm32"))]
 use std::time::{Duration, Instant};
-use winit::{dpi::LogicalSize, event::{self, WindowEvent}, event_loop::{ControlFlow, EventLoop}};
+use winit::{dpi::{LogicalSize, Size}, event::{self, WindowEvent}, event_loop::{ControlFlow, EventLoop}, window::Fullscreen};
 
 
 // shamelessly stolen from wgpu/examples and tweaked to work with these dependency versions
@@ -30,7 +30,113 @@ pub enum ShaderStage {
     Compute,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowMode {
+    Windowed,
+    Borderless,
+    /// Fullscreen with the largest video mode of the monitor
+    Exclusive,
+}
+
+impl WindowMode {
+    /// The mode F11 switches to
+    pub fn next(self) -> WindowMode {
+        match self {
+            WindowMode::Windowed => WindowMode::Borderless,
+            WindowMode::Borderless => WindowMode::Exclusive,
+            WindowMode::Exclusive => WindowMode::Windowed,
+        }
+    }
+}
+
+/// Presentation settings the example can change at runtime from `Framework::ui`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Display {
+    /// Unsupported modes fall back to `Fifo`, which every surface supports
+    pub present_mode: wgpu::PresentMode,
+    /// Frames per second, `None` for unlimited
+    pub frame_cap: Option<u32>,
+    pub window_mode: WindowMode,
+    /// Monitor name used for fullscreen, `None` for the one the window is on
+    pub monitor: Option<String>,
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self {
+            present_mode: wgpu::PresentMode::Mailbox,
+            frame_cap: Some(144),
+            window_mode: WindowMode::Windowed,
+            monitor: None,
+        }
+    }
+}
+
+fn fullscreen(window: &winit::window::Window, display: &Display) -> Option<Fullscreen> {
+    let monitor = display.monitor.as_ref().and_then(|name| {
+        let found = window.available_monitors().find(|m| m.name().as_ref() == Some(name));
+        if found.is_none() {
+            log::warn!("Monitor \"{}\" not found, using the current one", name);
+        }
+        found
+    });
+    match display.window_mode {
+        WindowMode::Windowed => None,
+        WindowMode::Borderless => Some(Fullscreen::Borderless(monitor)),
+        WindowMode::Exclusive => {
+            let monitor = monitor.or_else(|| window.current_monitor()).or_else(|| window.primary_monitor())?;
+            let video_mode = monitor.video_modes().max_by_key(|mode| {
+                let size = mode.size();
+                (size.width * size.height, mode.refresh_rate(), mode.bit_depth())
+            });
+            match video_mode {
+                Some(video_mode) => Some(Fullscreen::Exclusive(video_mode)),
+                None => {
+                    log::warn!("No video modes for exclusive fullscreen, using borderless");
+                    Some(Fullscreen::Borderless(Some(monitor)))
+                }
+            }
+        }
+    }
+}
+
+fn monitor_names(window: &winit::window::Window) -> Vec<String> {
+    window.available_monitors().filter_map(|m| m.name()).collect()
+}
+
+/// Window and adapter choices made before the example starts
+pub struct Settings {
+    pub title: String,
+    pub size: Size,
+    pub backend: wgpu::BackendBit,
+    pub power_preference: wgpu::PowerPreference,
+    pub display: Display,
+    /// Tab toggles it at runtime
+    pub show_ui: bool,
+    pub font: std::path::PathBuf,
+    pub ui_scale: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            title: String::new(),
+            size: LogicalSize::new(1600, 900).into(),
+            backend: wgpu::BackendBit::PRIMARY,
+            power_preference: wgpu::PowerPreference::default(),
+            display: Display::default(),
+            show_ui: true,
+            font: "./resources/fonts/Roboto-Light.ttf".into(),
+            ui_scale: 1.0,
+        }
+    }
+}
+
 pub trait Framework: 'static + Sized {
+    /// Passed through `run` to `init`
+    type Options;
+
     fn optional_features() -> wgpu::Features {
         wgpu::Features::empty()
     }
@@ -41,6 +147,7 @@ pub trait Framework: 'static + Sized {
         wgpu::Limits::default()
     }
     fn init(
+        options: Self::Options,
         sc_desc: &wgpu::SwapChainDescriptor,
         adapter: &wgpu::Adapter,
         device: &wgpu::Device,
@@ -55,16 +162,23 @@ pub trait Framework: 'static + Sized {
     fn update(&mut self, event: &WindowEvent);
     fn render(
         &mut self,
-        frame: &wgpu::SwapChainTexture,
+        view: &wgpu::TextureView,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         spawner: &Spawner,
     );
 
+    /// Changes to `display` are applied once the frame is presented,
+    /// `monitors` lists the names of the connected monitors
     fn ui(
         &mut self,
-        ui: &imgui::Ui
+        ui: &imgui::Ui,
+        display: &mut Display,
+        monitors: &[String],
     );
+
+    /// Called after the display settings changed from `previous`, from the UI or with F11
+    fn display_changed(&mut self, _display: &Display, _previous: &Display) {}
 }
 
 struct Setup {
@@ -78,33 +192,22 @@ struct Setup {
     queue: wgpu::Queue,
     imgui: imgui::Context,
     platform: imgui_winit_support::WinitPlatform,
+    show_ui: bool,
+    display: Display,
 }
 
-async fn setup<E: Framework>(title: &str) -> Setup {
-    #[cfg(not(target_arch = "wasm32"))]
-    {
-        #[cfg(debug_assertions)] {
-            env_logger::builder().filter(None, log::LevelFilter::Info).init();
-        };
-        
-
-        #[cfg(not(debug_assertions))] 
-        {
-            env_logger::builder().filter(None, log::LevelFilter::Warn).init();
-        };
-    };
-
-    log::info!("Logger started");
-
+async fn setup<E: Framework>(settings: &Settings) -> Setup {
     let event_loop = EventLoop::new();
     let mut builder = winit::window::WindowBuilder::new();
-    builder = builder.with_title(title).with_inner_size(LogicalSize::new(1600, 900));
+    builder = builder.with_title(&settings.title).with_inner_size(settings.size);
     #[cfg(windows_OFF)] // TODO
     {
         use winit::platform::windows::WindowBuilderExtWindows;
         builder = builder.with_no_redirection_bitmap(true);
     }
     let window = builder.build(&event_loop).unwrap();
+    log::info!("Monitors: {:?}", monitor_names(&window));
+    window.set_fullscreen(fullscreen(&window, &settings.display));
 
 
     #[cfg(target_arch = "wasm32")]
@@ -125,29 +228,8 @@ async fn setup<E: Framework>(title: &str) -> Setup {
 
     log::info!("Initializing the surface...");
 
-    let backend = if let Ok(backend) = std::env::var("WGPU_BACKEND") {
-        match backend.to_lowercase().as_str() {
-            "vulkan" => wgpu::BackendBit::VULKAN,
-            "metal" => wgpu::BackendBit::METAL,
-            "dx12" => wgpu::BackendBit::DX12,
-            "dx11" => wgpu::BackendBit::DX11,
-            "gl" => wgpu::BackendBit::GL,
-            "webgpu" => wgpu::BackendBit::BROWSER_WEBGPU,
-            other => panic!("Unknown backend: {}", other),
-        }
-    } else {
-        wgpu::BackendBit::PRIMARY
-    };
-    let power_preference = if let Ok(power_preference) = std::env::var("WGPU_POWER_PREF") {
-        match power_preference.to_lowercase().as_str() {
-            "low" => wgpu::PowerPreference::LowPower,
-            "high" => wgpu::PowerPreference::HighPerformance,
-            other => panic!("Unknown power preference: {}", other),
-        }
-    } else {
-        wgpu::PowerPreference::default()
-    };
-    let instance = wgpu::Instance::new(backend);
+    let power_preference = settings.power_preference;
+    let instance = wgpu::Instance::new(settings.backend);
     let (size, surface) = unsafe {
         let size = window.inner_size();
         let surface = instance.create_surface(&window);
@@ -173,6 +255,7 @@ async fn setup<E: Framework>(title: &str) -> Setup {
     imgui.style_mut().frame_rounding = 4.0;
     imgui.style_mut().window_rounding = 6.0;
     imgui.style_mut().frame_border_size = 1.0;
+    imgui.style_mut().scale_all_sizes(settings.ui_scale);
     let mut platform = imgui_winit_support::WinitPlatform::init(&mut imgui);
     platform.attach_window(
         imgui.io_mut(),
@@ -180,24 +263,37 @@ async fn setup<E: Framework>(title: &str) -> Setup {
         imgui_winit_support::HiDpiMode::Default,
     );
     imgui.set_ini_filename(None);
+    match crate::clipboard::init() {
+        Some(backend) => imgui.set_clipboard_backend(Box::new(backend)),
+        None => log::warn!("Failed to initialize clipboard"),
+    }
 
-    let font_size = (13.0 * hidpi_factor) as f32;
+    let font_size = (13.0 * hidpi_factor) as f32 * settings.ui_scale;
     imgui.io_mut().font_global_scale = (1.0 / hidpi_factor) as f32;
 
-    use std::io::Read;
-    let mut file = std::fs::File::open("./resources/fonts/Roboto-Light.ttf").unwrap();
-    let mut buf = Vec::new();
-    file.read_to_end(&mut buf).unwrap();
-    imgui.fonts().add_font(&[imgui::FontSource::TtfData {
-        data: buf.as_slice(),
-        size_pixels: 11.0,
-        config: Some(imgui::FontConfig {
-            oversample_h: 1,
-            pixel_snap_h: true,
-            size_pixels: font_size,
-            ..Default::default()
-        }),
-    }]);
+    match std::fs::read(&settings.font) {
+        Ok(buf) => {
+            imgui.fonts().add_font(&[imgui::FontSource::TtfData {
+                data: buf.as_slice(),
+                size_pixels: 11.0,
+                config: Some(imgui::FontConfig {
+                    oversample_h: 1,
+                    pixel_snap_h: true,
+                    size_pixels: font_size,
+                    ..Default::default()
+                }),
+            }]);
+        }
+        Err(e) => {
+            log::error!("Error loading font {}: {}, using the default font", settings.font.display(), e);
+            imgui.fonts().add_font(&[imgui::FontSource::DefaultFontData {
+                config: Some(imgui::FontConfig {
+                    size_pixels: font_size,
+                    ..Default::default()
+                }),
+            }]);
+        }
+    }
 
     let optional_features = E::optional_features();
     let required_features = E::required_features();
@@ -234,6 +330,8 @@ async fn setup<E: Framework>(title: &str) -> Setup {
         queue,
         imgui,
         platform,
+        show_ui: settings.show_ui,
+        display: settings.display.clone(),
     }
 }
 
@@ -249,7 +347,10 @@ fn start<E: Framework>(
         queue,
         mut imgui,
         mut platform,
+        mut show_ui,
+        mut display,
     }: Setup,
+    options: E::Options,
 ) {
     let spawner = Spawner::new();
 
@@ -259,7 +360,7 @@ fn start<E: Framework>(
         format: adapter.get_swap_chain_preferred_format(&surface),
         width: size.width,
         height: size.height,
-        present_mode: wgpu::PresentMode::Mailbox,
+        present_mode: display.present_mode,
     };
     let mut swap_chain = device.create_swap_chain(&surface, &sc_desc);
 
@@ -271,7 +372,9 @@ fn start<E: Framework>(
     let mut renderer = imgui_wgpu::Renderer::new(&mut imgui, &device, &queue, renderer_config);
 
     log::info!("Initializing the example...");
-    let mut example = E::init(&sc_desc, &adapter, &device, &queue);
+    let mut example = E::init(options, &sc_desc, &adapter, &device, &queue);
+    let mut applied = display.clone();
+    window.set_cursor_visible(show_ui);
 
     #[cfg(not(target_arch = "wasm32"))]
     let mut last_update_inst = Instant::now();
@@ -288,15 +391,18 @@ fn start<E: Framework>(
             event::Event::RedrawEventsCleared => {
                 #[cfg(not(target_arch = "wasm32"))]
                 {
-                    let target_frametime = Duration::from_secs_f64(1.0 / 144.0);
+                    let target_frametime = display.frame_cap.map(|fps| Duration::from_secs_f64(1.0 / fps as f64));
                     let time_since_last_frame = last_update_inst.elapsed();
-                    if time_since_last_frame >= target_frametime {
-                        window.request_redraw();
-                        last_update_inst = Instant::now();
-                    } else {
-                        *control_flow = ControlFlow::WaitUntil(
-                            Instant::now() + target_frametime - time_since_last_frame,
-                        );
+                    match target_frametime {
+                        Some(target) if time_since_last_frame < target => {
+                            *control_flow = ControlFlow::WaitUntil(
+                                Instant::now() + target - time_since_last_frame,
+                            );
+                        }
+                        _ => {
+                            window.request_redraw();
+                            last_update_inst = Instant::now();
+                        }
                     }
 
                     spawner.run_until_stalled();
@@ -328,6 +434,29 @@ fn start<E: Framework>(
                 | WindowEvent::CloseRequested => {
                     *control_flow = ControlFlow::Exit;
                 }
+                WindowEvent::KeyboardInput {
+                    input:
+                        event::KeyboardInput {
+                            virtual_keycode: Some(event::VirtualKeyCode::F11),
+                            state: event::ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } => {
+                    display.window_mode = display.window_mode.next();
+                }
+                WindowEvent::KeyboardInput {
+                    input:
+                        event::KeyboardInput {
+                            virtual_keycode: Some(event::VirtualKeyCode::Tab),
+                            state: event::ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } if !imgui.io().want_text_input => {
+                    show_ui = !show_ui;
+                    window.set_cursor_visible(show_ui);
+                }
                 _ => {
                     example.update(event);
                 }
@@ -344,43 +473,62 @@ fn start<E: Framework>(
                 };
                 
                 
-                example.render(&frame.output, &device, &queue, &spawner);
-
-                platform
-                    .prepare_frame(imgui.io_mut(), &window)
-                    .expect("Failed to prepare frame");
-                let ui = imgui.frame();
-                example.ui(&ui);
-                //example.render_imgui(&frame.output, &device, &queue, &spawner, &)
-
-                let mut encoder: wgpu::CommandEncoder =
-                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-
-                // if last_cursor != Some(ui.mouse_cursor()) {
-                //     last_cursor = Some(ui.mouse_cursor());
-                //     platform.prepare_render(&ui, &window);
-                // }
-
-                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                    label: None,
-                    color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
-                        attachment: &frame.output.view,
-                        resolve_target: None,
-                        ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Load,
-                            store: true,
-                        },
-                    }],
-                    depth_stencil_attachment: None,
-                });
-
-                renderer
-                    .render(ui.render(), &queue, &device, &mut rpass)
-                    .expect("Rendering failed");
-
-                drop(rpass);
+                example.render(&frame.output.view, &device, &queue, &spawner);
+
+                if show_ui {
+                    platform
+                        .prepare_frame(imgui.io_mut(), &window)
+                        .expect("Failed to prepare frame");
+                    let monitors = monitor_names(&window);
+                    let ui = imgui.frame();
+                    example.ui(&ui, &mut display, &monitors);
+                    //example.render_imgui(&frame.output, &device, &queue, &spawner, &)
+
+                    let mut encoder: wgpu::CommandEncoder =
+                        device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+                    // if last_cursor != Some(ui.mouse_cursor()) {
+                    //     last_cursor = Some(ui.mouse_cursor());
+                    //     platform.prepare_render(&ui, &window);
+                    // }
+
+                    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: None,
+                        color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                            attachment: &frame.output.view,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Load,
+                                store: true,
+                            },
+                        }],
+                        depth_stencil_attachment: None,
+                    });
+
+                    renderer
+                        .render(ui.render(), &queue, &device, &mut rpass)
+                        .expect("Rendering failed");
+
+                    drop(rpass);
+
+                    queue.submit(Some(encoder.finish()));
+                }
 
-                queue.submit(Some(encoder.finish()));
+                drop(frame);
+                if display != applied {
+                    if display.present_mode != applied.present_mode {
+                        log::info!("Switching present mode to {:?}", display.present_mode);
+                        sc_desc.present_mode = display.present_mode;
+                        swap_chain = device.create_swap_chain(&surface, &sc_desc);
+                    }
+                    // the swap chain follows through the Resized event
+                    if display.window_mode != applied.window_mode || display.monitor != applied.monitor {
+                        log::info!("Switching to {:?} on {:?}", display.window_mode, display.monitor);
+                        window.set_fullscreen(fullscreen(&window, &display));
+                    }
+                    example.display_changed(&display, &applied);
+                    applied = display.clone();
+                }
             }
             _ => {}
         }
@@ -427,17 +575,85 @@ impl Spawner {
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-pub fn run<E: Framework>(title: &str) {
-    let setup = pollster::block_on(setup::<E>(title));
-    start::<E>(setup);
+pub fn run<E: Framework>(settings: Settings, options: E::Options) {
+    let setup = pollster::block_on(setup::<E>(&settings));
+    start::<E>(setup, options);
+}
+
+/// Renders `frames` frames offscreen without a window and writes them to
+/// `output` as numbered PNG files
+#[cfg(not(target_arch = "wasm32"))]
+pub fn render_headless<E: Framework>(
+    settings: &Settings,
+    options: E::Options,
+    frames: u32,
+    output: &std::path::Path,
+) -> Result<(), String> {
+    let size: winit::dpi::PhysicalSize<u32> = settings.size.to_physical(1.0);
+    let (width, height) = (size.width.max(1), size.height.max(1));
+    std::fs::create_dir_all(output).map_err(|e| format!("Error creating {}: {}", output.display(), e))?;
+
+    let instance = wgpu::Instance::new(settings.backend);
+    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+        power_preference: settings.power_preference,
+        compatible_surface: None,
+    }))
+    .ok_or("No suitable GPU adapters found on the system!")?;
+    let adapter_info = adapter.get_info();
+    println!("Using {} ({:?})", adapter_info.name, adapter_info.backend);
+
+    let (device, queue) = pollster::block_on(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            label: None,
+            features: (E::optional_features() & adapter.features()) | E::required_features(),
+            limits: E::required_limits(),
+        },
+        None,
+    ))
+    .map_err(|e| format!("Unable to request a device: {}", e))?;
+
+    // the swap chain is never created, the example only reads the format and size
+    let sc_desc = wgpu::SwapChainDescriptor {
+        usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        width,
+        height,
+        present_mode: settings.display.present_mode,
+    };
+    let target = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Headless Target"),
+        size: wgpu::Extent3d { width, height, depth: 1 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: sc_desc.format,
+        usage: wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::COPY_SRC,
+    });
+    let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let spawner = Spawner::new();
+    let mut example = E::init(options, &sc_desc, &adapter, &device, &queue);
+    for frame in 0..frames {
+        example.render(&view, &device, &queue, &spawner);
+        let pixels = crate::util::read_texture(&device, &queue, &target, width, height)
+            .map_err(|e| format!("Frame {}: {}", frame, e))?;
+
+        let path = output.join(format!("frame_{:06}.png", frame));
+        image::save_buffer(&path, &pixels, width, height, image::ColorType::Rgba8)
+            .map_err(|e| format!("Error writing {}: {}", path.display(), e))?;
+        if frame % 100 == 0 {
+            log::info!("Rendered frame {}/{}", frame + 1, frames);
+        }
+    }
+    println!("Wrote {} frames to {}", frames, output.display());
+    Ok(())
 }
 
 #[cfg(target_arch = "wasm32")]
-pub fn run<E: Example>(title: &str) {
-    let title = title.to_owned();
+pub fn run<E: Framework>(settings: Settings, options: E::Options) {
     wasm_bindgen_futures::spawn_local(async move {
-        let setup = setup::<E>(&title).await;
-        start::<E>(setup);
+        let setup = setup::<E>(&settings).await;
+        start::<E>(setup, options);
     });
 }
 
@@ -0,0 +1,54 @@
+use crate::params::Params;
+
+/// Undo history of a single preset
+#[derive(Debug, Default)]
+pub struct UndoStack {
+    undo: Vec<Params>,
+    redo: Vec<Params>,
+    /// Last state recorded as a step, edits are compared against it
+    committed: Option<Params>,
+}
+
+impl UndoStack {
+    /// Starts from `committed`, so the first observed edit can be undone back to it
+    pub fn with_committed(committed: Params) -> Self {
+        UndoStack { committed: Some(committed), ..Default::default() }
+    }
+
+    /// Records a step once `current` differs from the last committed state and no
+    /// widget is active, so a whole drag gesture becomes a single step
+    pub fn observe(&mut self, current: &Params, editing: bool) {
+        match &self.committed {
+            None => self.committed = Some(current.clone()),
+            Some(committed) if !editing && committed != current => {
+                let previous = std::mem::replace(&mut self.committed, Some(current.clone()));
+                self.undo.extend(previous);
+                self.redo.clear();
+            }
+            _ => {}
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Returns the state to restore, `current` becomes redoable
+    pub fn undo(&mut self, current: &Params) -> Option<Params> {
+        let previous = self.undo.pop()?;
+        self.redo.push(current.clone());
+        self.committed = Some(previous.clone());
+        Some(previous)
+    }
+
+    pub fn redo(&mut self, current: &Params) -> Option<Params> {
+        let next = self.redo.pop()?;
+        self.undo.push(current.clone());
+        self.committed = Some(next.clone());
+        Some(next)
+    }
+}
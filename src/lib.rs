@@ -0,0 +1,66 @@
+//! Physarum ("slime mold") simulation on wgpu.
+//!
+//! `Simulation` owns the GPU resources and records its passes into the
+//! caller's command encoders, so it can run inside any wgpu 0.7 app:
+//!
+//! ```no_run
+//! # fn frame(device: &wgpu::Device, queue: &wgpu::Queue, view: &wgpu::TextureView) {
+//! use moldsim::simulation::{Simulation, SimulationDescriptor};
+//!
+//! let mut simulation = Simulation::new(device, &SimulationDescriptor::default());
+//! let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+//! simulation.step(&mut encoder);
+//! simulation.draw(&mut encoder, view);
+//! queue.submit(Some(encoder.finish()));
+//! # }
+//! ```
+//!
+//! `Renderer` draws the trails into a render pass of your own, into any format
+//! and viewport, optionally blended over what the pass already holds:
+//!
+//! ```no_run
+//! # fn frame(device: &wgpu::Device, simulation: &moldsim::Simulation, view: &wgpu::TextureView) {
+//! use moldsim::renderer::{Renderer, RendererDescriptor, Viewport};
+//!
+//! let renderer = Renderer::new(device, simulation, &RendererDescriptor {
+//!     format: wgpu::TextureFormat::Rgba8UnormSrgb,
+//!     transparent: true,
+//! });
+//! let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+//! let viewport = Viewport { x: 0.0, y: 0.0, width: 800.0, height: 450.0 };
+//! renderer.draw(simulation, &mut encoder, view, Some(viewport), None);
+//! # }
+//! ```
+//!
+//! `params` holds the presets and their uniforms. The winit/imgui app
+//! framework is behind the `framework` feature, and the remote controls are
+//! behind `osc`, `api`, `midi` (the input port only), `script` and `preset-code`.
+
+pub mod params;
+pub mod uniform;
+pub mod history;
+pub mod morph;
+pub mod playlist;
+pub mod timeline;
+pub mod modulation;
+pub mod simulation;
+pub mod renderer;
+pub mod util;
+pub mod remote;
+pub mod watch;
+pub mod midi;
+#[cfg(feature = "osc")]
+pub mod osc;
+#[cfg(feature = "api")]
+pub mod api;
+#[cfg(feature = "script")]
+pub mod script;
+#[cfg(feature = "preset-code")]
+pub mod preset_code;
+#[cfg(feature = "framework")]
+pub mod framework;
+#[cfg(feature = "framework")]
+mod clipboard;
+
+pub use simulation::{Simulation, SimulationDescriptor};
+pub use renderer::{Renderer, RendererDescriptor, Viewport};
@@ -1,567 +1,202 @@
-use params::ParamManager;
-use rand::Rng;
-use wgpu::util::DeviceExt;
-use crate::uniform::Uniform;
-
-#[path = "./framework.rs"]
-mod framework;
-mod util;
-mod params;
-mod uniform;
-
-const PARTICLES_PER_GROUP: u32 = 64;
-const SCREEN_SIZE: (u32, u32) = (3200, 1800);
-
-struct SimBuffers {
-    particle_buffers: Vec<wgpu::Buffer>,
-    trail_textures: Vec<wgpu::Texture>,
-    vertices_buffer: wgpu::Buffer, 
-    particle_uniform: wgpu::Buffer,
-    decay_uniform: wgpu::Buffer,
-    diffuse_uniform: wgpu::Buffer,
-    render_uniform: wgpu::Buffer,
-}
+use explore::Explorer;
+use framework::WindowMode;
+use playlist::{Playlist, PlaylistEntry};
+use timeline::Easing;
+use modulation::Feature;
+use remote::Command;
+use preset_file::{Conflict, Format};
+use params::{ParamManager, ParamKind, ParamValue, Reflect};
+use moldsim::{api, framework, midi, modulation, osc, params, playlist, preset_code, remote, script, timeline, util, watch};
+use moldsim::simulation::{Simulation, SimulationDescriptor, TIMESTEP};
+
+mod explore;
+mod preset_file;
+mod cli;
+mod config;
+mod audio;
+
+/// Longest measured frame time, so a stalled window doesn't skip transitions ahead
+const MAX_FRAME_TIME: f32 = 0.25;
 
-struct SimBindGroups {
-    particle_bind_groups: Vec<wgpu::BindGroup>,
-    trail_decay_bind_groups: Vec<wgpu::BindGroup>,
-    trail_diffuse_bind_groups: Vec<wgpu::BindGroup>,
-    render_bind_groups: Vec<wgpu::BindGroup>,
+struct MoldSim {
+    params: ParamManager,
+    /// User presets file, built-in presets are compiled in
+    params_path: std::path::PathBuf,
+    params_watcher: watch::FileWatcher,
+    explorer: Explorer,
+    simulation: Simulation,
+    uniform_uploads: usize,
+    frame_uploads: usize,
+    show_debug: bool,
+    /// Error or status line shown in the config window
+    message: Option<String>,
+    /// File or directory used by preset import/export
+    preset_path: imgui::ImString,
+    /// Name being typed into the Name field, applied when the edit is committed
+    preset_name: Option<imgui::ImString>,
+    conflict: Conflict,
+    code_error: Option<String>,
+    config: config::AppConfig,
+    /// Window mode, monitor and MIDI setup picked while running, saved apart from the config
+    state: config::State,
+    state_path: std::path::PathBuf,
+    scheduler: playlist::Scheduler,
+    playhead: timeline::Playhead,
+    /// Field path picked for a new timeline track
+    track_path: usize,
+    audio: Option<audio::AudioTrack>,
+    /// Analysis frames since the track started, fractional when frame times are measured
+    audio_position: f32,
+    audio_playing: bool,
+    audio_path: imgui::ImString,
+    modulator: modulation::Modulator,
+    /// Field path picked for a new audio mapping
+    mapping_path: usize,
+    script: Option<script::Script>,
+    script_running: bool,
+    script_path: imgui::ImString,
+    midi: Option<midi::MidiInput>,
+    /// Input port names, listed when the MIDI panel asks for them
+    midi_ports: Vec<String>,
+    /// Clicking a slider picks it for the next control change
+    midi_learning: bool,
+    /// Field path bound to the next control change
+    midi_learn: Option<String>,
+    /// Live control changes captured for replaying, timed in simulation seconds
+    midi_capture: Option<midi::Recording>,
+    midi_time: f32,
+    midi_playback: Option<midi::Playback>,
+    midi_path: imgui::ImString,
+    osc: Option<osc::OscServer>,
+    api: Option<api::ApiServer>,
+    /// Last preset sent to WebSocket clients
+    broadcast_params: Option<params::Params>,
+    /// Frame and time of the last stats event
+    broadcast_stats: (usize, std::time::Instant),
+    /// Format and size of the frames, screenshots are drawn with the same pipeline
+    target: (wgpu::TextureFormat, u32, u32),
+    /// Set when particles should be respawned on the next frame
+    respawn: bool,
+    /// Stops the compute passes and the clocks, the last frame keeps being drawn
+    paused: bool,
+    /// Advances one frame while paused
+    step: bool,
+    /// Advances the clocks by `TIMESTEP` every frame instead of the measured frame time
+    fixed_step: bool,
+    last_frame: Option<std::time::Instant>,
+    /// Set by `ui`, edits are recorded from `render` while the UI is hidden
+    ui_drawn: bool,
 }
 
-struct SimPipelines {
-    particle_bind_group_layout: wgpu::BindGroupLayout,
-    diffuse_bind_group_layout: wgpu::BindGroupLayout,
-    decay_bind_group_layout: wgpu::BindGroupLayout,
-    render_bind_group_layout: wgpu::BindGroupLayout,
-    particle_compute_pipeline: wgpu::ComputePipeline,
-    trail_decay_compute_pipeline: wgpu::ComputePipeline,
-    trail_diffuse_compute_pipeline: wgpu::ComputePipeline,
-    render_pipeline: wgpu::RenderPipeline,
-}
 
-struct MoldSim {
+/// Startup choices resolved from the command line
+struct SimOptions {
     params: ParamManager,
-    buffers: SimBuffers,
-    bind_groups: SimBindGroups,
-    pipelines: SimPipelines,
-    particle_work_group_count: u32,
-    screen_work_group_count: (u32, u32),
-    frame_num: usize,
+    params_path: std::path::PathBuf,
+    seed: Option<u64>,
+    config: config::AppConfig,
+    state: config::State,
+    state_path: std::path::PathBuf,
+    /// Played instead of `params.playlist` without replacing it, from `--playlist`
+    playlist: Option<Playlist>,
+    /// Start playing the playlist
+    play: bool,
+    /// Start playing `params.timeline`
+    animate: bool,
+    /// Modulates `params.audio_mappings`, playing from the start
+    audio: Option<audio::AudioTrack>,
+    /// Runs the `on_frame` hook of the script
+    script: Option<script::Script>,
+    midi: Option<midi::MidiInput>,
+    /// Replayed through the MIDI bindings
+    midi_playback: Option<midi::Playback>,
+    osc: Option<osc::OscServer>,
+    api: Option<api::ApiServer>,
+    /// Set for `--render`, so frames line up with the audio and playlist timing
+    fixed_step: bool,
 }
 
-
 impl framework::Framework for MoldSim {
+    type Options = SimOptions;
 
     fn init(
+        options: SimOptions,
         sc_desc: &wgpu::SwapChainDescriptor,
-        adapter: &wgpu::Adapter,
+        _adapter: &wgpu::Adapter,
         device: &wgpu::Device,
         _queue: &wgpu::Queue,
     ) -> Self {
 
-        let params = params::ParamManager::from_json("./resources/params.json");
-
-        let mut flags = wgpu::ShaderFlags::VALIDATION;
-        match adapter.get_info().backend {
-            wgpu::Backend::Vulkan | wgpu::Backend::Metal | wgpu::Backend::Gl => {
-                flags |= wgpu::ShaderFlags::EXPERIMENTAL_TRANSLATION;
-            }
-            _ => {} //TODO
+        let SimOptions { mut params, params_path, seed, config, state, state_path, playlist, play, animate, audio, script, midi, midi_playback, osc, api, fixed_step } = options;
+        let mut scheduler = playlist.map_or_else(playlist::Scheduler::default, playlist::Scheduler::with_playlist);
+        if play {
+            scheduler.play(0, &mut params);
         }
+        log::info!("User presets: {}", params_path.display());
 
-        let (compute_shader, decay_shader, diffuse_shader, draw_shader) = 
-        (
-            crate::util::create_shader(device, "./resources/spirv/compute.spv"),
-            crate::util::create_shader(device, "./resources/spirv/decay.spv"),
-            crate::util::create_shader(device, "./resources/spirv/diffuse.spv"),
-            crate::util::create_shader(device, "./resources/spirv/draw.spv")
-        );
-
-        let texture_size = wgpu::Extent3d {
-            width: SCREEN_SIZE.0,
-            height: SCREEN_SIZE.1,
-            depth: 1,
-        };
-
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::Repeat,
-            address_mode_v: wgpu::AddressMode::Repeat,
-            address_mode_w: wgpu::AddressMode::Repeat,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
+        let simulation = Simulation::new(device, &SimulationDescriptor {
+            format: sc_desc.format,
+            max_particles: params.global.max_particles,
+            seed,
+            params: params.effective().into_owned(),
         });
-
-        let pipelines = {
-
-            log::info!("Creating particle bind group...");
-            let particle_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStage::COMPUTE,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: params.current().particle.memsize(),
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStage::COMPUTE,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Storage { read_only: true },
-                            has_dynamic_offset: false,
-                            min_binding_size: wgpu::BufferSize::new((params.global.max_particles * 16) as _),
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 2,
-                        visibility: wgpu::ShaderStage::COMPUTE,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Storage { read_only: false },
-                            has_dynamic_offset: false,
-                            min_binding_size: wgpu::BufferSize::new((params.global.max_particles * 16) as _),
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 3,
-                        visibility: wgpu::ShaderStage::COMPUTE,
-                        ty: wgpu::BindingType::StorageTexture {
-                            access: wgpu::StorageTextureAccess::ReadOnly,
-                            format: wgpu::TextureFormat::R32Float,
-                            view_dimension: wgpu::TextureViewDimension::D2
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 4,
-                        visibility: wgpu::ShaderStage::COMPUTE,
-                        ty: wgpu::BindingType::StorageTexture {
-                            access: wgpu::StorageTextureAccess::WriteOnly,
-                            format: wgpu::TextureFormat::R32Float,
-                            view_dimension: wgpu::TextureViewDimension::D2
-                        },
-                        count: None,
-                    },
-                ],
-                label: None,
-            });
-
-            log::info!("Creating decay bind group...");
-            let decay_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStage::COMPUTE,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: params.current().decay.memsize(),
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStage::COMPUTE,
-                        ty: wgpu::BindingType::StorageTexture {
-                            access: wgpu::StorageTextureAccess::ReadOnly,
-                            format: wgpu::TextureFormat::R32Float,
-                            view_dimension: wgpu::TextureViewDimension::D2
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 2,
-                        visibility: wgpu::ShaderStage::COMPUTE,
-                        ty: wgpu::BindingType::StorageTexture {
-                            access: wgpu::StorageTextureAccess::WriteOnly,
-                            format: wgpu::TextureFormat::R32Float,
-                            view_dimension: wgpu::TextureViewDimension::D2
-                        },
-                        count: None,
-                    },
-                ],
-                label: None,
-            });
-
-            log::info!("Creating diffuse bind group...");
-            let diffuse_bind_group_layout = 
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStage::COMPUTE,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: params.current().diffuse.memsize(),
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStage::COMPUTE,
-                        ty: wgpu::BindingType::StorageTexture {
-                            access: wgpu::StorageTextureAccess::ReadOnly,
-                            format: wgpu::TextureFormat::R32Float,
-                            view_dimension: wgpu::TextureViewDimension::D2
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 2,
-                        visibility: wgpu::ShaderStage::COMPUTE,
-                        ty: wgpu::BindingType::StorageTexture {
-                            access: wgpu::StorageTextureAccess::WriteOnly,
-                            format: wgpu::TextureFormat::R32Float,
-                            view_dimension: wgpu::TextureViewDimension::D2
-                        },
-                        count: None,
-                    },
-                ],
-                label: None,
-            });
-
-            log::info!("Creating render bind group...");
-            let render_bind_group_layout = 
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStage::FRAGMENT,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: params.current().render.memsize(),
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStage::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            multisampled: false,
-                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 2,
-                        visibility: wgpu::ShaderStage::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler {
-                            comparison: false,
-                            filtering: false,
-                        },
-                        count: None,
-                    },
-                ],
-                label: None,
-            });
-
-            log::info!("Creating particle pipeline layout...");
-            let particle_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("particle"),
-                bind_group_layouts: &[&particle_bind_group_layout],
-                push_constant_ranges: &[],
-            });
-
-            log::info!("Creating decay pipeline layout...");
-            let decay_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("decay"),
-                bind_group_layouts: &[&decay_bind_group_layout],
-                push_constant_ranges: &[],
-            });
-
-            log::info!("Creating diffuse pipeline layout...");
-            let diffuse_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("diffuse"),
-                bind_group_layouts: &[&diffuse_bind_group_layout],
-                push_constant_ranges: &[],
-            });
-
-            log::info!("Creating render pipeline layout...");
-            let render_pipeline_layout =
-                device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                    label: Some("render"),
-                    bind_group_layouts: &[&render_bind_group_layout],
-                    push_constant_ranges: &[],
-            });
-
-            log::info!("Creating particle pipeline...");
-            let particle_compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-                label: Some("Particle compute pipeline"),
-                layout: Some(&particle_pipeline_layout),
-                module: &compute_shader,
-                entry_point: "main",
-            });
-    
-            log::info!("Creating decay pipeline...");
-            let trail_decay_compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-                label: Some("Decay compute pipeline"),
-                layout: Some(&decay_pipeline_layout),
-                module: &decay_shader,
-                entry_point: "main",
-            });
-    
-            log::info!("Creating diffuse pipeline...");
-            let trail_diffuse_compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-                label: Some("Diffuse compute pipeline"),
-                layout: Some(&diffuse_pipeline_layout),
-                module: &diffuse_shader,
-                entry_point: "main",
-            });
-
-            log::info!("Creating render pipeline...");
-            let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: None,
-                layout: Some(&render_pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &draw_shader,
-                    entry_point: "main",
-                    buffers: &[
-                        wgpu::VertexBufferLayout {
-                            array_stride: 2 * 4,
-                            step_mode: wgpu::InputStepMode::Vertex,
-                            attributes: &wgpu::vertex_attr_array![0 => Float2],
-                        },
-                    ],
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &draw_shader,
-                    entry_point: "main",
-                    targets: &[sc_desc.format.into()],
-                }),
-                primitive: wgpu::PrimitiveState::default(),
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState::default(),
-            });
-
-            SimPipelines {
-                particle_bind_group_layout,
-                diffuse_bind_group_layout,
-                decay_bind_group_layout,
-                render_bind_group_layout,
-                particle_compute_pipeline,
-                trail_decay_compute_pipeline,
-                trail_diffuse_compute_pipeline,
-                render_pipeline
-            }
-
-        };
-        
-
-        let buffers = {
-            let vertex_buffer_data = [-1.0f32, -1.0, 1.0, -1.0, 1.0, 1.0, -1.0, -1.0, 1.0, 1.0, -1.0, 1.0];
-            let vertices_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Vertex Buffer"),
-                contents: bytemuck::bytes_of(&vertex_buffer_data),
-                usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
-            });
-            let mut particle_buffers = Vec::<wgpu::Buffer>::new();
-            let mut trail_textures = Vec::<wgpu::Texture>::new();
-    
-            let mut rng = rand::thread_rng();
-            let mut initial_particle_data = vec![0.0f32; (4 * params.global.max_particles) as usize];
-            for particle_instance_chunk in initial_particle_data.chunks_mut(4) {
-                particle_instance_chunk[0] = rng.gen::<f32>();
-                particle_instance_chunk[1] = rng.gen::<f32>();
-                particle_instance_chunk[2] = rng.gen::<f32>() * 2.0 - 1.0;
-                particle_instance_chunk[3] = rng.gen::<f32>() * 2.0 - 1.0;
-            }
-
-            for i in 0..2 {
-                particle_buffers.push(
-                    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                        label: Some(&format!("Particle Buffer {}", i)),
-                        contents: bytemuck::cast_slice(&initial_particle_data),
-                        usage: wgpu::BufferUsage::STORAGE
-                            | wgpu::BufferUsage::COPY_DST
-                            | wgpu::BufferUsage::COPY_SRC,
-                    }),
-                );
-    
-                trail_textures.push(device.create_texture(
-                    &wgpu::TextureDescriptor {
-                        label: Some(&format!("Trail Texture {}", i)),
-                        size: texture_size,
-                        mip_level_count: 1, 
-                        sample_count: 1,
-                        dimension: wgpu::TextureDimension::D2,
-                        format: wgpu::TextureFormat::R32Float,
-                        usage: wgpu::TextureUsage::STORAGE | wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_SRC | wgpu::TextureUsage::COPY_DST,
-                    }
-                ));
-            }
-
-            let particle_uniform = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Simulation Parameter Buffer"),
-                contents: params.current().particle.to_bytes(),
-                usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
-            });
-
-            let decay_uniform = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Simulation Parameter Buffer"),
-                contents: params.current().decay.to_bytes(),
-                usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
-            });
-
-            let diffuse_uniform = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Simulation Parameter Buffer"),
-                contents: params.current().diffuse.to_bytes(),
-                usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
-            });
-
-            let render_uniform = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Simulation Parameter Buffer"),
-                contents: params.current().render.to_bytes(),
-                usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
-            });
-
-            SimBuffers {
-                vertices_buffer,
-                particle_buffers,
-                trail_textures,
-                particle_uniform,
-                decay_uniform,
-                diffuse_uniform,
-                render_uniform
-            }
-        };
-
-        let bind_groups = {
-
-            let mut particle_bind_groups = Vec::<wgpu::BindGroup>::new();
-            let mut trail_decay_bind_groups = Vec::<wgpu::BindGroup>::new();
-            let mut trail_diffuse_bind_groups = Vec::<wgpu::BindGroup>::new();
-            let mut render_bind_groups = Vec::<wgpu::BindGroup>::new();
-
-            let desc = Default::default();
-
-            for i in 0..2 {
-                particle_bind_groups.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
-                    layout: &pipelines.particle_bind_group_layout,
-                    entries: &[
-                        wgpu::BindGroupEntry {
-                            binding: 0,
-                            resource: buffers.particle_uniform.as_entire_binding(),
-                        },
-                        wgpu::BindGroupEntry {
-                            binding: 1,
-                            resource: buffers.particle_buffers[i].as_entire_binding(),
-                        },
-                        wgpu::BindGroupEntry {
-                            binding: 2,
-                            resource: buffers.particle_buffers[(i + 1) % 2].as_entire_binding(), // bind to opposite buffer
-                        },
-                        wgpu::BindGroupEntry {
-                            binding: 3,
-                            resource: wgpu::BindingResource::TextureView(&buffers.trail_textures[i].create_view(&desc)),
-                        },
-                        wgpu::BindGroupEntry {
-                            binding: 4,
-                            resource: wgpu::BindingResource::TextureView(&buffers.trail_textures[(i + 1) % 2].create_view(&desc)), // bind to opposite buffer
-                        },
-                    ],
-                    label: None,
-                }));
-    
-                trail_decay_bind_groups.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
-                    layout: &pipelines.decay_bind_group_layout,
-                    entries: &[
-                        wgpu::BindGroupEntry {
-                            binding: 0,
-                            resource: buffers.decay_uniform.as_entire_binding(),
-                        },
-                        wgpu::BindGroupEntry {
-                            binding: 1,
-                            resource: wgpu::BindingResource::TextureView(&buffers.trail_textures[(i + 1) % 2].create_view(&desc)),
-                        },
-                        wgpu::BindGroupEntry {
-                            binding: 2,
-                            resource: wgpu::BindingResource::TextureView(&buffers.trail_textures[i].create_view(&desc)), // bind to opposite buffer
-                        },
-                    ],
-                    label: None,
-                }));
-    
-                trail_diffuse_bind_groups.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
-                    layout: &pipelines.diffuse_bind_group_layout,
-                    entries: &[
-                        wgpu::BindGroupEntry {
-                            binding: 0,
-                            resource: buffers.diffuse_uniform.as_entire_binding(),
-                        },
-                        wgpu::BindGroupEntry {
-                            binding: 1,
-                            resource: wgpu::BindingResource::TextureView(&buffers.trail_textures[i].create_view(&desc)),
-                        },
-                        wgpu::BindGroupEntry {
-                            binding: 2,
-                            resource: wgpu::BindingResource::TextureView(&buffers.trail_textures[(i + 1) % 2].create_view(&desc)), // bind to opposite buffer
-                        },
-                    ],
-                    label: None,
-                }));
-    
-                render_bind_groups.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
-                    layout: &pipelines.render_bind_group_layout,
-                    entries: &[
-                        wgpu::BindGroupEntry {
-                            binding: 0,
-                            resource: buffers.render_uniform.as_entire_binding(),
-                        },
-                        wgpu::BindGroupEntry {
-                            binding: 1,
-                            resource: wgpu::BindingResource::TextureView(&buffers.trail_textures[(i + 1) % 2].create_view(&desc)),
-                        },
-                        wgpu::BindGroupEntry {
-                            binding: 2,
-                            resource: wgpu::BindingResource::Sampler(&sampler),
-                        },
-                    ],
-                    label: None,
-                }));
-            }
-
-            SimBindGroups {
-                particle_bind_groups,
-                trail_decay_bind_groups,
-                trail_diffuse_bind_groups,
-                render_bind_groups,
-            }
-
-        };
-
-        // calculates number of work groups from PARTICLES_PER_GROUP constant
-        let particle_work_group_count =
-            ((params.current().particle.num_particles as f32) / (PARTICLES_PER_GROUP as f32)).ceil() as u32;
-
-        let screen_work_group_count: (u32, u32) = 
-            ((SCREEN_SIZE.0 as f32 / 16.0).ceil() as u32, (SCREEN_SIZE.1 as f32 / 16.0).ceil() as u32);
-
-        log::info!("Particle work group count: {:?}", (particle_work_group_count, particle_work_group_count));
-        log::info!("Screen work group count: {:?}", screen_work_group_count);
+        // the sliders and clamps follow the buffers if the presets file asked for too many
+        params.global.max_particles = simulation.max_particles();
 
         MoldSim {
             params,
-            buffers,
-            bind_groups,
-            pipelines,
-            particle_work_group_count,
-            screen_work_group_count,
-            frame_num: 0,
+            params_watcher: watch::FileWatcher::new(params_path.clone()),
+            params_path,
+            explorer: Explorer::new(),
+            simulation,
+            uniform_uploads: 0,
+            frame_uploads: 0,
+            show_debug: false,
+            message: None,
+            preset_path: imgui::ImString::new("./presets"),
+            preset_name: None,
+            conflict: Conflict::Rename,
+            code_error: None,
+            config,
+            state,
+            state_path,
+            scheduler,
+            playhead: timeline::Playhead { playing: animate, changed: true, ..Default::default() },
+            track_path: 0,
+            audio_playing: audio.is_some(),
+            audio_path: imgui::ImString::new(audio.as_ref().map_or(String::new(), |a| a.path.display().to_string())),
+            audio,
+            audio_position: 0.0,
+            modulator: Default::default(),
+            mapping_path: 0,
+            script_running: script.is_some(),
+            script_path: imgui::ImString::new(script.as_ref().map_or(String::new(), |s| s.path.display().to_string())),
+            script,
+            midi,
+            midi_ports: Vec::new(),
+            midi_learning: false,
+            midi_learn: None,
+            midi_capture: None,
+            midi_time: 0.0,
+            midi_path: imgui::ImString::new("./midi.txt"),
+            midi_playback,
+            osc,
+            api,
+            broadcast_params: None,
+            broadcast_stats: (0, std::time::Instant::now()),
+            target: (sc_desc.format, sc_desc.width, sc_desc.height),
+            respawn: false,
+            paused: false,
+            step: false,
+            fixed_step,
+            last_frame: None,
+            ui_drawn: false,
+        }
+    }
+
+    fn display_changed(&mut self, display: &framework::Display, previous: &framework::Display) {
+        if self.state.update_window(display, previous) {
+            self.save_state();
         }
     }
 
@@ -571,104 +206,146 @@ impl framework::Framework for MoldSim {
     /// resize is called on WindowEvent::Resized events
     fn resize(
         &mut self,
-        _sc_desc: &wgpu::SwapChainDescriptor,
+        sc_desc: &wgpu::SwapChainDescriptor,
         _device: &wgpu::Device,
         _queue: &wgpu::Queue,
     ) {
-
-
-
+        self.target = (sc_desc.format, sc_desc.width, sc_desc.height);
     }
 
     fn render(
         &mut self,
-        frame: &wgpu::SwapChainTexture,
+        view: &wgpu::TextureView,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         _spawner: &framework::Spawner,
     ) {
 
-        self.particle_work_group_count = ((self.params.current().particle.num_particles as f32) / (PARTICLES_PER_GROUP as f32)).ceil() as u32;
+        let now = std::time::Instant::now();
+        let dt = match self.last_frame.replace(now) {
+            Some(last) if !self.fixed_step => (now - last).as_secs_f32().min(MAX_FRAME_TIME),
+            _ => TIMESTEP,
+        };
 
-        // update uniforms
-        // TODO: only update when value is changed
-        queue.write_buffer(&self.buffers.particle_uniform, 0, self.params.current().particle.to_bytes());
-        queue.write_buffer(&self.buffers.decay_uniform, 0, self.params.current().decay.to_bytes());
-        queue.write_buffer(&self.buffers.diffuse_uniform, 0, self.params.current().diffuse.to_bytes());
+        if self.params_watcher.poll() {
+            log::info!("Reloading {}", self.params_path.display());
+            self.message = self.params.reload(&self.params_path).err();
+        }
+        if self.script.as_mut().map_or(false, |script| script.poll()) {
+            // the new version may leave alone what the old one changed
+            self.params.mark_all_dirty();
+        }
+        let mut commands: Vec<Command> = self.osc.iter().flat_map(|osc| osc.poll()).collect();
+        commands.extend(self.script.iter_mut().flat_map(|script| script.take_commands()));
+        for command in commands {
+            self.apply_command(command);
+        }
+        let requests: Vec<api::Request> = self.api.iter().flat_map(|api| api.poll()).collect();
+        let mut screenshots = Vec::new();
+        for request in requests {
+            match request.call {
+                api::Call::Screenshot => screenshots.push(request),
+                _ => self.answer(request),
+            }
+        }
 
-        let r = &self.params.current().render;
-        let vec: Vec<f32> = vec![
-            r.color_1[0], r.color_1[1], r.color_1[2], r.color_2[0], r.color_2[1], r.color_2[2], r.color_pow, r.cutoff
-        ];
-        queue.write_buffer(&self.buffers.render_uniform, 0, bytemuck::cast_slice(vec.as_slice()));
+        let running = !self.paused || std::mem::take(&mut self.step);
+        // live input still edits while paused, recordings keep simulated time
+        let mut events: Vec<midi::ControlChange> = self.midi.iter().flat_map(|midi| midi.poll()).collect();
+        if let Some(capture) = &mut self.midi_capture {
+            let time = self.midi_time;
+            capture.events.extend(events.iter().map(|event| (time, *event)));
+        }
+        if running {
+            if let Some(playback) = &mut self.midi_playback {
+                events.extend(playback.tick(dt));
+            }
+            self.midi_time += dt;
+        }
+        for event in events {
+            self.handle_midi(event);
+        }
+        if running {
+            self.respawn |= self.scheduler.tick(dt, &mut self.params);
+        }
+        if self.respawn {
+            self.respawn = false;
+            self.simulation.respawn(queue);
+        }
+        if running {
+            self.params.tick(dt);
+            self.playhead.tick(dt, &self.params.timeline);
+        }
+        let mut params = self.params.effective().into_owned();
+        // a stopped timeline leaves its last values on screen until the sliders change them
+        let animated = if self.playhead.playing || self.playhead.changed {
+            self.params.timeline.apply(self.playhead.time, &mut params)
+        } else {
+            [false; 4]
+        };
+        let mut modulated = [false; 4];
+        if let Some(track) = self.audio.as_ref().filter(|_| self.audio_playing && running) {
+            match track.features(self.audio_position as usize) {
+                Some(features) => {
+                    let max_particles = self.params.global.max_particles;
+                    modulated = self.modulator.apply(&self.params.audio_mappings, features, &mut params, max_particles);
+                    self.audio_position += dt / track.frame_time;
+                }
+                None => {
+                    // the track ended, go back to the unmodulated values
+                    self.audio_playing = false;
+                    self.modulator.reset();
+                    self.params.mark_all_dirty();
+                }
+            }
+        }
+        let mut scripted = [false; 4];
+        if let (Some(script), true) = (self.script.as_mut(), self.script_running) {
+            // still evaluated while paused so edits go through the script
+            let dt = if running { dt } else { 0.0 };
+            scripted = script.run(dt, &mut params, self.params.global.max_particles);
+        }
 
+        // update uniforms of changed sections only
+        let mut dirty = self.params.take_dirty();
+        if std::mem::take(&mut self.playhead.changed) {
+            dirty.iter_mut().zip(animated.iter()).for_each(|(d, a)| *d |= *a);
+        }
+        dirty.iter_mut().zip(modulated.iter()).for_each(|(d, m)| *d |= *m);
+        dirty.iter_mut().zip(scripted.iter()).for_each(|(d, s)| *d |= *s);
+        self.frame_uploads = self.simulation.write_params(queue, &params, dirty);
+        self.uniform_uploads += self.frame_uploads;
 
-        let color_attachments = [wgpu::RenderPassColorAttachmentDescriptor {
-            attachment: &frame.view,
-            resolve_target: None,
-            ops: wgpu::Operations {
-                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                store: true,
-            },
-        }];
-        let render_pass_descriptor = wgpu::RenderPassDescriptor {
-            label: None,
-            color_attachments: &color_attachments,
-            depth_stencil_attachment: None,
-        };
 
         let mut command_encoder =
             device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
 
-        command_encoder.push_debug_group("compute particle movement");
-        {
-            let mut cpass =
-                command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
-            cpass.set_pipeline(&self.pipelines.particle_compute_pipeline);
-            cpass.set_bind_group(0, &self.bind_groups.particle_bind_groups[self.frame_num % 2], &[]);
-            cpass.dispatch(self.particle_work_group_count, 1, 1);
-        }
-        command_encoder.pop_debug_group();
-
-        if self.params.global.post_enabled {
-            command_encoder.push_debug_group("compute trail decay");
-            {
-                let mut cpass =
-                    command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
-                cpass.set_pipeline(&self.pipelines.trail_decay_compute_pipeline);
-                cpass.set_bind_group(0, &self.bind_groups.trail_decay_bind_groups[self.frame_num % 2], &[]);
-                cpass.dispatch(self.screen_work_group_count.0, self.screen_work_group_count.1, 1);
-            }
-            command_encoder.pop_debug_group();
-            command_encoder.push_debug_group("compute trail diffuse");
-            {
-                let mut cpass =
-                    command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
-                cpass.set_pipeline(&self.pipelines.trail_diffuse_compute_pipeline);
-                cpass.set_bind_group(0, &self.bind_groups.trail_diffuse_bind_groups[self.frame_num % 2], &[]);
-                cpass.dispatch(self.screen_work_group_count.0, self.screen_work_group_count.1, 1);
-            }
-            command_encoder.pop_debug_group();
-        }
-
-        command_encoder.push_debug_group("render to screen");
-        {
-            let mut rpass = command_encoder.begin_render_pass(&render_pass_descriptor);
-            rpass.set_pipeline(&self.pipelines.render_pipeline);
-            rpass.set_vertex_buffer(0, self.buffers.vertices_buffer.slice(..));
-            rpass.set_bind_group(0, &self.bind_groups.render_bind_groups[self.frame_num % 2], &[]);
-            rpass.draw(0..6, 0..1);
-        }
-        command_encoder.pop_debug_group();
-
-        self.frame_num += 1;
-
+        if running {
+            self.simulation.post_enabled = self.params.global.post_enabled;
+            self.simulation.step(&mut command_encoder);
+        }
+        self.simulation.draw(&mut command_encoder, view);
         queue.submit(Some(command_encoder.finish()));
+
+        for request in screenshots {
+            let reply = match self.screenshot(device, queue) {
+                Ok(png) => api::Reply::Png(png),
+                Err(e) => api::Reply::Error(500, e),
+            };
+            request.reply(reply);
+        }
+        self.broadcast();
+        // remote, MIDI and script edits get their own undo steps without the UI
+        if !std::mem::take(&mut self.ui_drawn) {
+            self.params.observe_edits(false);
+        }
     }
 
     fn ui(
         &mut self,
-        ui: &imgui::Ui
+        ui: &imgui::Ui,
+        display: &mut framework::Display,
+        monitors: &[String],
     ) {
         
         use imgui::Condition;
@@ -684,90 +361,1127 @@ impl framework::Framework for MoldSim {
             .menu_bar(true)
             .build(&ui, || {
 
+                let mut confirm_delete = false;
                 if let Some(token) = ui.begin_menu_bar() {
+                    if let Some(menu) = ui.begin_menu(im_str!("Edit"), true) {
+                        if imgui::MenuItem::new(im_str!("Undo"))
+                            .shortcut(im_str!("Ctrl+Z"))
+                            .enabled(self.params.can_undo())
+                            .build(ui) {
+                            self.params.undo();
+                        }
+                        if imgui::MenuItem::new(im_str!("Redo"))
+                            .shortcut(im_str!("Ctrl+Y"))
+                            .enabled(self.params.can_redo())
+                            .build(ui) {
+                            self.params.redo();
+                        }
+                        menu.end(ui);
+                    }
                     if imgui::MenuItem::new(im_str!("New")).build(ui) {
                         self.params.new();
                     }
                     if imgui::MenuItem::new(im_str!("Save")).build(ui) {
-                        self.params.save("./resources/params.json");
+                        self.message = self.params.save(&self.params_path).err();
+                        self.params_watcher.sync();
+                    }
+                    if let Some(menu) = ui.begin_menu(im_str!("Preset"), true) {
+                        let count = self.params.params.len();
+                        if imgui::MenuItem::new(im_str!("Duplicate")).build(ui) {
+                            self.params.duplicate();
+                        }
+                        if imgui::MenuItem::new(im_str!("Delete..."))
+                            .enabled(count > 1 && !self.params.current().builtin)
+                            .build(ui) {
+                            confirm_delete = true;
+                        }
+                        ui.separator();
+                        if imgui::MenuItem::new(im_str!("Move Up"))
+                            .enabled(self.params.current > 0)
+                            .build(ui) {
+                            self.params.move_by(-1);
+                        }
+                        if imgui::MenuItem::new(im_str!("Move Down"))
+                            .enabled(self.params.current + 1 < count)
+                            .build(ui) {
+                            self.params.move_by(1);
+                        }
+                        ui.separator();
+                        if imgui::MenuItem::new(im_str!("Revert to Saved")).build(ui) {
+                            self.message = self.params.revert(&self.params_path).err();
+                        }
+                        menu.end(ui);
+                    }
+                    if imgui::MenuItem::new(im_str!("Reset")).build(ui) {
+                        self.respawn = true;
                     }
+                    imgui::MenuItem::new(im_str!("Pause")).build_with_ref(ui, &mut self.paused);
+                    if imgui::MenuItem::new(im_str!("Step")).enabled(self.paused).build(ui) {
+                        self.step = true;
+                    }
+                    imgui::MenuItem::new(im_str!("Debug")).build_with_ref(ui, &mut self.show_debug);
                     token.end(ui);
                 }
 
+                if confirm_delete {
+                    ui.open_popup(im_str!("Delete preset?"));
+                }
+                imgui::PopupModal::new(im_str!("Delete preset?"))
+                    .always_auto_resize(true)
+                    .build(ui, || {
+                        ui.text(format!("Delete \"{}\"? This can't be undone.", self.params.current().name));
+                        if ui.button(im_str!("Delete"), [80.0, 0.0]) {
+                            self.params.delete();
+                            ui.close_current_popup();
+                        }
+                        ui.same_line(0.0);
+                        if ui.button(im_str!("Cancel"), [80.0, 0.0]) {
+                            ui.close_current_popup();
+                        }
+                    });
+
+                if let Some(message) = &self.message {
+                    ui.text_colored([1.0, 0.8, 0.4, 1.0], &imgui::ImString::new(message));
+                }
+
                 let status = if self.params.global.post_enabled {
                     im_str!("Post-processing enabled")
                 } else {
                     im_str!("Post-processing disabled")
                 };
                 
-                imgui::ComboBox::new(im_str!("Preset"))
-                    .flags(imgui::ComboBoxFlags::empty())
-                    //.preview_value(&imgui::ImString::new(self.params.current_name()))
-                    .build_simple(ui, &mut self.params.current, &self.params.params[..], &|p: &crate::params::Params| {
-                        std::borrow::Cow::from(imgui::ImString::new(&p.name))
-                });
+                let mut selected = self.params.current;
+                if preset_combo(ui, im_str!("Preset"), &mut selected, &self.params.params) {
+                    self.params.select(selected);
+                }
+                imgui::Slider::new(im_str!("Transition (s)"))
+                    .range(0.0..=10.0)
+                    .build(ui, &mut self.params.global.transition_time);
 
-                let mut str = imgui::ImString::new(&self.params.current().name);
-                imgui::InputText::new(ui, im_str!("Name"), &mut str)
-                    .no_horizontal_scroll(true)
-                    .build();
-                self.params.current_mut().name = str.to_string();
+                if self.params.current().builtin {
+                    ui.text_disabled(im_str!("Built-in preset (read-only), edits go to a copy"));
+                } else {
+                    let mut name = self.preset_name.take()
+                        .unwrap_or_else(|| imgui::ImString::new(&self.params.current().name));
+                    let entered = imgui::InputText::new(ui, im_str!("Name"), &mut name)
+                        .no_horizontal_scroll(true)
+                        .resize_buffer(true)
+                        .enter_returns_true(true)
+                        .build();
+                    // trimmed and made unique once committed, not on every keystroke
+                    if entered || ui.is_item_deactivated_after_edit() {
+                        self.params.rename(name.to_str());
+                    } else if ui.is_item_active() {
+                        self.preset_name = Some(name);
+                    }
+                }
 
                 
                 if ui.radio_button_bool(status, true) {
                     self.params.global.post_enabled = !self.params.global.post_enabled
                 }
 
-                unsafe {
-                    ui.text(im_str!("Particle Compute"));
-                    imgui::Slider::new(im_str!("Num Particles"))
-                        .range(0u32..=self.params.global.max_particles-1)
-                        .build(ui, &mut self.params.current_mut().particle.num_particles);
-                    imgui::Slider::new(im_str!("Trail Power"))
-                        .range(0.0..=64.0)
-                        .build(ui, &mut self.params.current_mut().particle.trail_power);
-                    imgui::Slider::new(im_str!("Speed"))
-                        .range(0.0..=15.0)
-                        .build(ui, &mut self.params.current_mut().particle.speed);
-                    imgui::Slider::new(im_str!("Sensor Angle"))
-                        .range(0.0..=1.5)
-                        .build(ui, &mut self.params.current_mut().particle.sensor_angle);
-                    imgui::Slider::new(im_str!("Sensor Distance"))
-                        .range(0.0..=0.01)
-                        .build(ui, &mut self.params.current_mut().particle.sensor_distance);
-                    imgui::Slider::new(im_str!("Turn Speed"))
-                        .range(0.0..=3.14)
-                        .build(ui, &mut self.params.current_mut().particle.turn_speed);
-                    ui.separator();
-                    ui.text(im_str!("Decay Compute"));
-                    imgui::Slider::new(im_str!("Decay Factor"))
-                        .range(0.5..=1.0)
-                        .build(ui, &mut self.params.current_mut().decay.decay_rate);
-                    ui.separator();
-                    ui.text(im_str!("Diffuse Compute"));
-                    imgui::Slider::new(im_str!("Diffuse Amount"))
+                if imgui::CollapsingHeader::new(im_str!("A/B Crossfader")).build(ui) {
+                    let fade = &mut self.params.crossfade;
+                    let mut changed = ui.checkbox(im_str!("Enabled"), &mut fade.enabled);
+                    changed |= preset_combo(ui, im_str!("A"), &mut fade.a, &self.params.params);
+                    changed |= preset_combo(ui, im_str!("B"), &mut fade.b, &self.params.params);
+                    changed |= imgui::Slider::new(im_str!("A -> B"))
                         .range(0.0..=1.0)
-                        .build(ui, &mut self.params.current_mut().diffuse.diffuse_amount);
-                    ui.separator();
-                    ui.text(im_str!("Render"));
-                    imgui::Slider::new(im_str!("Color Power"))
-                        .range(0.2..=1.0)
-                        .build(ui, &mut self.params.current_mut().render.color_pow);
-                    imgui::ColorPicker::new(im_str!("Color 1"), &mut self.params.current_mut().render.color_1)
-                        .input_mode(imgui::ColorEditInputMode::Rgb)
-                        .mode(imgui::ColorPickerMode::HueWheel)
-                        .build(ui);
-                    imgui::ColorPicker::new(im_str!("Color 2"), &mut self.params.current_mut().render.color_2)
-                        .input_mode(imgui::ColorEditInputMode::Rgb)
-                        .mode(imgui::ColorPickerMode::HueWheel)
-                        .build(ui);
+                        .build(ui, &mut fade.amount);
+                    if changed {
+                        self.params.mark_all_dirty();
+                    }
+                }
 
+                if imgui::CollapsingHeader::new(im_str!("Playlist")).build(ui) {
+                    if self.scheduler.is_playing() {
+                        if ui.button(im_str!("Stop"), [0.0, 0.0]) {
+                            self.scheduler.stop();
+                        }
+                    } else if ui.button(im_str!("Play"), [0.0, 0.0]) {
+                        self.respawn |= self.scheduler.play(0, &mut self.params);
+                    }
+                    if !self.scheduler.is_temporary() {
+                        ui.same_line(0.0);
+                        ui.checkbox(im_str!("Loop"), &mut self.params.playlist.looping);
+                    }
+                    if self.scheduler.is_playing() {
+                        let playlist = self.scheduler.playlist(&self.params);
+                        ui.same_line(0.0);
+                        ui.text(format!(
+                            "{}/{}, {:.0}s left",
+                            self.scheduler.position() + 1,
+                            playlist.entries.len(),
+                            self.scheduler.remaining(playlist),
+                        ));
+                    }
+                    if self.scheduler.is_temporary() {
+                        ui.text("Playing the --playlist presets, which are not saved");
+                        if ui.button(im_str!("Use saved playlist"), [0.0, 0.0]) {
+                            self.scheduler.clear_temporary();
+                        }
+                    } else {
+                        let ParamManager { params: presets, playlist, .. } = &mut self.params;
+                        let count = playlist.entries.len();
+                        let (mut jump, mut remove, mut swap) = (None, None, None);
+                        for (i, entry) in playlist.entries.iter_mut().enumerate() {
+                            let id = ui.push_id(i as i32);
+                            ui.separator();
+                            match presets.iter().position(|p| p.name == entry.name) {
+                                Some(mut index) => {
+                                    if preset_combo(ui, im_str!("Preset"), &mut index, presets) {
+                                        entry.name = presets[index].name.clone();
+                                    }
+                                }
+                                None => ui.text_colored([1.0, 0.4, 0.4, 1.0], format!("Missing preset \"{}\"", entry.name)),
+                            }
+                            imgui::Slider::new(im_str!("Duration (s)"))
+                                .range(1.0..=600.0)
+                                .build(ui, &mut entry.duration);
+                            imgui::Slider::new(im_str!("Transition (s)"))
+                                .range(0.0..=30.0)
+                                .build(ui, &mut entry.transition);
+                            ui.checkbox(im_str!("Respawn"), &mut entry.respawn);
+                            ui.same_line(0.0);
+                            if ui.button(im_str!("Go"), [0.0, 0.0]) {
+                                jump = Some(i);
+                            }
+                            ui.same_line(0.0);
+                            if ui.button(im_str!("Up"), [0.0, 0.0]) && i > 0 {
+                                swap = Some((i, i - 1));
+                            }
+                            ui.same_line(0.0);
+                            if ui.button(im_str!("Down"), [0.0, 0.0]) && i + 1 < count {
+                                swap = Some((i, i + 1));
+                            }
+                            ui.same_line(0.0);
+                            if ui.button(im_str!("Remove"), [0.0, 0.0]) {
+                                remove = Some(i);
+                            }
+                            id.pop(ui);
+                        }
+                        if let Some((a, b)) = swap {
+                            playlist.entries.swap(a, b);
+                        }
+                        if let Some(i) = remove {
+                            playlist.entries.remove(i);
+                        }
+                        ui.separator();
+                        if ui.button(im_str!("Add current preset"), [0.0, 0.0]) {
+                            let entry = PlaylistEntry {
+                                transition: self.params.global.transition_time,
+                                ..PlaylistEntry::new(&self.params.current().name)
+                            };
+                            self.params.playlist.entries.push(entry);
+                        }
+                        if let Some(i) = jump {
+                            self.respawn |= self.scheduler.play(i, &mut self.params);
+                        }
+                    }
                 }
-                
+
+                if imgui::CollapsingHeader::new(im_str!("Timeline")).build(ui) {
+                    self.timeline_ui(ui);
+                }
+
+                if imgui::CollapsingHeader::new(im_str!("Audio")).build(ui) {
+                    self.audio_ui(ui);
+                }
+
+                if imgui::CollapsingHeader::new(im_str!("Script")).build(ui) {
+                    self.script_ui(ui);
+                }
+
+                if imgui::CollapsingHeader::new(im_str!("MIDI")).build(ui) {
+                    self.midi_ui(ui);
+                }
+
+                if imgui::CollapsingHeader::new(im_str!("Explore")).build(ui) {
+                    let max_particles = self.params.global.max_particles;
+                    let mut rng = rand::thread_rng();
+                    let mut variant = None;
+                    if ui.button(im_str!("Randomize"), [0.0, 0.0]) {
+                        let mut params = self.params.current().clone();
+                        explore::randomize(&mut params, max_particles, &mut rng);
+                        variant = Some(params);
+                    }
+                    ui.same_line(0.0);
+                    if ui.button(im_str!("Mutate"), [0.0, 0.0]) {
+                        let mut params = self.params.current().clone();
+                        explore::mutate(&mut params, self.explorer.amount, max_particles, &mut rng);
+                        variant = Some(params);
+                    }
+                    imgui::Slider::new(im_str!("Amount"))
+                        .range(0.0..=0.5)
+                        .build(ui, &mut self.explorer.amount);
+                    if let Some(params) = variant {
+                        self.explorer.push(self.params.current(), params.clone());
+                        self.params.apply(&params);
+                    }
+
+                    let mut jump = None;
+                    for (i, params) in self.explorer.history.iter().enumerate() {
+                        let label = imgui::ImString::new(format!("{}##variant{}", params.name, i));
+                        if imgui::Selectable::new(&label)
+                            .selected(self.explorer.selected == Some(i))
+                            .build(ui) {
+                            jump = Some(i);
+                        }
+                    }
+                    if let Some(i) = jump {
+                        self.explorer.selected = Some(i);
+                        self.params.apply(&self.explorer.history[i]);
+                    }
+                    if let Some(i) = self.explorer.selected {
+                        if ui.button(im_str!("Promote to preset"), [0.0, 0.0]) {
+                            self.params.add(self.explorer.history[i].clone());
+                        }
+                        ui.same_line(0.0);
+                    }
+                    if ui.button(im_str!("Clear history"), [0.0, 0.0]) {
+                        self.explorer = Explorer { amount: self.explorer.amount, ..Explorer::new() };
+                    }
+                }
+
+                if imgui::CollapsingHeader::new(im_str!("Import / Export")).build(ui) {
+                    imgui::InputText::new(ui, im_str!("Path"), &mut self.preset_path)
+                        .resize_buffer(true)
+                        .build();
+                    let path = std::path::PathBuf::from(self.preset_path.to_str());
+                    for &(label, format) in &[(im_str!("Export JSON"), Format::Json), (im_str!("Export TOML"), Format::Toml)] {
+                        if ui.button(label, [0.0, 0.0]) {
+                            self.message = Some(match preset_file::export(self.params.current(), &path, format) {
+                                Ok(file) => format!("Exported to {}", file.display()),
+                                Err(e) => e,
+                            });
+                        }
+                        ui.same_line(0.0);
+                    }
+                    if ui.button(im_str!("Import"), [0.0, 0.0]) {
+                        let (presets, mut errors) = preset_file::read_all(&path);
+                        let count = preset_file::import(&mut self.params, presets, self.conflict);
+                        errors.insert(0, format!("Imported {} preset(s)", count));
+                        self.message = Some(errors.join("\n"));
+                    }
+                    ui.text(im_str!("On name conflict"));
+                    ui.radio_button(im_str!("Rename"), &mut self.conflict, Conflict::Rename);
+                    ui.same_line(0.0);
+                    ui.radio_button(im_str!("Replace"), &mut self.conflict, Conflict::Replace);
+                    ui.same_line(0.0);
+                    ui.radio_button(im_str!("Skip"), &mut self.conflict, Conflict::Skip);
+
+                    if ui.button(im_str!("Copy code"), [0.0, 0.0]) {
+                        let code = preset_code::encode(self.params.current());
+                        ui.set_clipboard_text(&imgui::ImString::new(code));
+                        self.code_error = None;
+                    }
+                    ui.same_line(0.0);
+                    if ui.button(im_str!("Paste code"), [0.0, 0.0]) {
+                        let code = ui.clipboard_text().map(|c| c.to_string()).unwrap_or_default();
+                        match preset_code::decode(&code, self.params.global.max_particles) {
+                            Ok(params) => {
+                                self.params.add(params);
+                                self.code_error = None;
+                            }
+                            Err(e) => self.code_error = Some(e),
+                        }
+                    }
+                    if let Some(error) = &self.code_error {
+                        ui.text_colored([1.0, 0.4, 0.4, 1.0], &imgui::ImString::new(error));
+                    }
+                }
+
+                if imgui::CollapsingHeader::new(im_str!("Display")).build(ui) {
+                    ui.text(im_str!("Present mode"));
+                    ui.radio_button(im_str!("Fifo (vsync)"), &mut display.present_mode, wgpu::PresentMode::Fifo);
+                    ui.same_line(0.0);
+                    ui.radio_button(im_str!("Mailbox"), &mut display.present_mode, wgpu::PresentMode::Mailbox);
+                    ui.same_line(0.0);
+                    ui.radio_button(im_str!("Immediate"), &mut display.present_mode, wgpu::PresentMode::Immediate);
+                    ui.text_disabled(im_str!("Unsupported modes fall back to Fifo"));
+
+                    let mut unlimited = display.frame_cap.is_none();
+                    if ui.checkbox(im_str!("Unlimited FPS"), &mut unlimited) {
+                        display.frame_cap = if unlimited { None } else { Some(144) };
+                    }
+                    if let Some(fps) = &mut display.frame_cap {
+                        imgui::Slider::new(im_str!("Frame cap"))
+                            .range(10u32..=360)
+                            .build(ui, fps);
+                    }
+
+                    ui.text(im_str!("Window (F11)"));
+                    ui.radio_button(im_str!("Windowed"), &mut display.window_mode, WindowMode::Windowed);
+                    ui.same_line(0.0);
+                    ui.radio_button(im_str!("Borderless"), &mut display.window_mode, WindowMode::Borderless);
+                    ui.same_line(0.0);
+                    ui.radio_button(im_str!("Exclusive"), &mut display.window_mode, WindowMode::Exclusive);
+
+                    let current = im_str!("Current");
+                    let preview = display.monitor.as_ref().map(imgui::ImString::new);
+                    if let Some(combo) = imgui::ComboBox::new(im_str!("Monitor"))
+                        .preview_value(preview.as_deref().unwrap_or(current))
+                        .begin(ui) {
+                        if imgui::Selectable::new(current).selected(display.monitor.is_none()).build(ui) {
+                            display.monitor = None;
+                        }
+                        for name in monitors {
+                            let selected = display.monitor.as_ref() == Some(name);
+                            if imgui::Selectable::new(&imgui::ImString::new(name)).selected(selected).build(ui) {
+                                display.monitor = Some(name.clone());
+                            }
+                        }
+                        combo.end(ui);
+                    }
+                }
+                ui.separator();
+
+                if self.midi_learning {
+                    let hint = match &self.midi_learn {
+                        Some(path) => format!("MIDI learn: move a control to bind {}", path),
+                        None => "MIDI learn: click a slider".to_owned(),
+                    };
+                    ui.text_colored([1.0, 0.8, 0.4, 1.0], &imgui::ImString::new(hint));
+                }
+                if let Some(num_particles) = self.params.global.num_particles {
+                    ui.text_disabled(&imgui::ImString::new(format!("Particle count fixed at {} by --particles", num_particles)));
+                }
+                let max_particles = self.params.global.max_particles;
+                let mut changed = [false; 4];
+                let mut clicked = None;
+                // edited on a copy so a built-in is only copied once something changes
+                let mut edited = self.params.current().clone();
+                for (i, section) in edited.sections_mut().iter_mut().enumerate() {
+                    if i > 0 {
+                        ui.separator();
+                    }
+                    changed[i] = edit_section(ui, &mut **section, max_particles, &mut clicked);
+                }
+                if changed.iter().any(|c| *c) {
+                    let name = self.params.current_editable().name.clone();
+                    *self.params.current_mut() = params::Params { name, builtin: false, ..edited };
+                    for (i, _) in changed.iter().enumerate().filter(|(_, c)| **c) {
+                        self.params.mark_dirty(i);
+                    }
+                }
+                if let Some(path) = clicked.filter(|path| self.midi_learning && midi::bindable(path)) {
+                    self.midi_learn = Some(path);
+                }
+        });
+
+        // text inputs handle their own undo
+        let io = ui.io();
+        if io.key_ctrl && !io.want_text_input {
+            if ui.is_key_pressed(imgui::Key::Z) {
+                if io.key_shift { self.params.redo() } else { self.params.undo() }
+            } else if ui.is_key_pressed(imgui::Key::Y) {
+                self.params.redo();
+            }
+        }
+        self.params.observe_edits(ui.is_any_item_active());
+        self.ui_drawn = true;
+
+        if self.show_debug {
+            imgui::Window::new(im_str!("Debug"))
+                .position([ui.io().display_size[0] - 10.0, 10.0], Condition::Always)
+                .position_pivot([1.0, 0.0])
+                .title_bar(false)
+                .resizable(false)
+                .movable(false)
+                .always_auto_resize(true)
+                .bg_alpha(0.35)
+                .build(&ui, || {
+                    ui.text(format!("Frame: {}", self.simulation.frame()));
+                    ui.text(format!("FPS: {:.1}", ui.io().framerate));
+                    ui.text(format!("Uniform uploads: {}", self.uniform_uploads));
+                    ui.text(format!("Uploads this frame: {}", self.frame_uploads));
+                });
+        }
+    }
+}
+
+impl MoldSim {
+    /// Draws the current frame offscreen and encodes it as PNG
+    fn screenshot(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<Vec<u8>, String> {
+        let (format, width, height) = self.target;
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("screenshot"),
+            size: wgpu::Extent3d { width, height, depth: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::COPY_SRC,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut command_encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.simulation.draw(&mut command_encoder, &view);
+        queue.submit(Some(command_encoder.finish()));
+
+        let mut pixels = util::read_texture(device, queue, &texture, width, height)?;
+        if let wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb = format {
+            pixels.chunks_exact_mut(4).for_each(|pixel| pixel.swap(0, 2));
+        }
+        let mut png = Vec::new();
+        image::png::PngEncoder::new(&mut png)
+            .encode(&pixels, width, height, image::ColorType::Rgba8)
+            .map_err(|e| e.to_string())?;
+        Ok(png)
+    }
+
+    /// Answers an API request, screenshots are taken after the frame is drawn
+    fn answer(&mut self, request: api::Request) {
+        let reply = match &request.call {
+            api::Call::GetParams => match serde_json::to_value(self.params.current()) {
+                Ok(value) => api::Reply::Json(value),
+                Err(e) => api::Reply::Error(500, e.to_string()),
+            },
+            api::Call::PutParams(params) => match params.clone().clamped(self.params.global.max_particles) {
+                Ok(params) => {
+                    self.params.apply(&params);
+                    api::Reply::Json(serde_json::json!({ "ok": true }))
+                }
+                Err(e) => api::Reply::Error(400, e),
+            },
+            api::Call::Presets => {
+                let presets: Vec<_> = self.params.params.iter()
+                    .map(|p| serde_json::json!({ "name": p.name, "builtin": p.builtin }))
+                    .collect();
+                api::Reply::Json(serde_json::json!({ "current": self.params.current, "presets": presets }))
+            }
+            api::Call::Command(command) => {
+                self.apply_command(command.clone());
+                api::Reply::Json(serde_json::json!({ "ok": true }))
+            }
+            api::Call::Screenshot => api::Reply::Error(500, "screenshots are answered after drawing".to_owned()),
+        };
+        request.reply(reply);
+    }
+
+    /// Sends preset changes and, about once a second, frame stats to WebSocket clients
+    fn broadcast(&mut self) {
+        let api = match self.api.as_ref().filter(|api| api.has_listeners()) {
+            Some(api) => api,
+            None => return,
+        };
+        if self.broadcast_params.as_ref() != Some(self.params.current()) {
+            let params = self.params.current().clone();
+            if let Ok(value) = serde_json::to_value(&params) {
+                api.broadcast(&serde_json::json!({ "type": "params", "params": value }));
+            }
+            self.broadcast_params = Some(params);
+        }
+        let (frame, time) = self.broadcast_stats;
+        let elapsed = time.elapsed().as_secs_f32();
+        if elapsed >= 1.0 {
+            api.broadcast(&serde_json::json!({
+                "type": "stats",
+                "frame": self.simulation.frame(),
+                "fps": (self.simulation.frame() - frame) as f32 / elapsed,
+                "uploads": self.uniform_uploads,
+                "paused": self.paused,
+            }));
+            self.broadcast_stats = (self.simulation.frame(), std::time::Instant::now());
+        }
+    }
+
+    fn save_state(&mut self) {
+        if let Err(e) = self.state.save(&self.state_path) {
+            self.message = Some(e);
+        }
+    }
+
+    /// Binds the control change while learning, otherwise sets the bound fields
+    fn handle_midi(&mut self, event: midi::ControlChange) {
+        if let Some(path) = self.midi_learn.take() {
+            midi::bind(&mut self.config.midi.bindings, &event, &path);
+            self.state.midi.bindings = Some(self.config.midi.bindings.clone());
+            self.save_state();
+            return;
+        }
+        let max_particles = self.params.global.max_particles;
+        for command in midi::commands(&event, &self.config.midi.bindings, max_particles) {
+            self.apply_command(command);
+        }
+    }
+
+    fn apply_command(&mut self, command: Command) {
+        match command {
+            Command::Set { path, value } => {
+                if !self.params.set_path(&path, value) {
+                    log::warn!("Can't set {} to {:?}", path, value);
+                }
+            }
+            Command::Preset(name) => {
+                if !self.params.select_by_name(&name) {
+                    log::warn!("Unknown preset \"{}\"", name);
+                }
+            }
+            Command::PresetIndex(index) => self.params.select(index),
+            Command::Reset => self.respawn = true,
+            Command::Pause(paused) => self.paused = paused,
+            Command::Step => self.step = true,
+        }
+    }
+
+    fn midi_ui(&mut self, ui: &imgui::Ui) {
+        use imgui::im_str;
+
+        match &self.midi {
+            Some(input) => {
+                ui.text(format!("Connected to {}", input.port));
+                ui.same_line(0.0);
+                if ui.small_button(im_str!("Disconnect")) {
+                    self.midi = None;
+                }
+            }
+            None => {
+                if ui.small_button(im_str!("List inputs")) {
+                    match midi::MidiInput::ports() {
+                        Ok(ports) => self.midi_ports = ports,
+                        Err(e) => self.message = Some(e),
+                    }
+                }
+                let mut connect = None;
+                for (i, port) in self.midi_ports.iter().enumerate() {
+                    let id = ui.push_id(i as i32);
+                    if ui.small_button(im_str!("Connect")) {
+                        connect = Some(port.clone());
+                    }
+                    ui.same_line(0.0);
+                    ui.text(port);
+                    id.pop(ui);
+                }
+                if let Some(port) = connect {
+                    match midi::MidiInput::connect(Some(port.as_str())) {
+                        Ok(input) => {
+                            self.config.midi.port = Some(input.port.clone());
+                            self.state.midi.port = Some(input.port.clone());
+                            self.midi = Some(input);
+                            self.save_state();
+                        }
+                        Err(e) => self.message = Some(e),
+                    }
+                }
+            }
+        }
+
+        ui.separator();
+        if ui.checkbox(im_str!("Learn"), &mut self.midi_learning) && !self.midi_learning {
+            self.midi_learn = None;
+        }
+        let mut remove = None;
+        for (i, binding) in self.config.midi.bindings.iter().enumerate() {
+            let id = ui.push_id(i as i32);
+            ui.text(format!("CC {} ch {} -> {}", binding.controller, binding.channel, binding.path));
+            ui.same_line(0.0);
+            if ui.small_button(im_str!("Remove")) {
+                remove = Some(i);
+            }
+            id.pop(ui);
+        }
+        if let Some(i) = remove {
+            self.config.midi.bindings.remove(i);
+            self.state.midi.bindings = Some(self.config.midi.bindings.clone());
+            self.save_state();
+        }
+
+        ui.separator();
+        imgui::InputText::new(ui, im_str!("Recording"), &mut self.midi_path)
+            .resize_buffer(true)
+            .build();
+        let path = std::path::PathBuf::from(self.midi_path.to_str());
+        match &self.midi_capture {
+            Some(capture) => {
+                let stop = ui.button(im_str!("Stop and save"), [0.0, 0.0]);
+                ui.same_line(0.0);
+                ui.text(format!("{} events", capture.events.len()));
+                if stop {
+                    if let Err(e) = capture.save(&path) {
+                        self.message = Some(e);
+                    }
+                    self.midi_capture = None;
+                }
+            }
+            None => {
+                if ui.button(im_str!("Record"), [0.0, 0.0]) {
+                    self.midi_capture = Some(midi::Recording::default());
+                    self.midi_time = 0.0;
+                }
+            }
+        }
+        ui.same_line(0.0);
+        if ui.button(im_str!("Replay"), [0.0, 0.0]) {
+            match midi::Recording::load(&path) {
+                Ok(recording) => self.midi_playback = Some(midi::Playback::new(recording)),
+                Err(e) => self.message = Some(e),
+            }
+        }
+        if let Some(playback) = &self.midi_playback {
+            if playback.finished() {
+                self.midi_playback = None;
+            } else {
+                ui.same_line(0.0);
+                ui.text(format!("{:.1}s / {:.1}s", playback.time, playback.recording.duration()));
+            }
+        }
+    }
+
+    fn script_ui(&mut self, ui: &imgui::Ui) {
+        use imgui::im_str;
+
+        imgui::InputText::new(ui, im_str!("Rhai file"), &mut self.script_path)
+            .resize_buffer(true)
+            .build();
+        if ui.button(im_str!("Load##script"), [0.0, 0.0]) {
+            let script = script::Script::load(std::path::Path::new(self.script_path.to_str()));
+            self.script_running = script.error().is_none();
+            self.script = Some(script);
+            self.params.mark_all_dirty();
+        }
+        if let Some(script) = &mut self.script {
+            ui.same_line(0.0);
+            if ui.checkbox(im_str!("Run"), &mut self.script_running) && !self.script_running {
+                // go back to the unscripted values
+                self.params.mark_all_dirty();
+            }
+            ui.same_line(0.0);
+            if ui.button(im_str!("Restart##script"), [0.0, 0.0]) {
+                script.time = 0.0;
+            }
+            ui.text(format!("t = {:.2}s", script.time));
+            match script.error() {
+                Some(error) => ui.text_colored([1.0, 0.4, 0.4, 1.0], &imgui::ImString::new(error)),
+                None => ui.text_disabled(im_str!("Reloads when the file is saved")),
+            }
+        }
+    }
+
+    fn audio_ui(&mut self, ui: &imgui::Ui) {
+        use imgui::im_str;
+
+        imgui::InputText::new(ui, im_str!("WAV file"), &mut self.audio_path)
+            .resize_buffer(true)
+            .build();
+        if ui.button(im_str!("Load"), [0.0, 0.0]) {
+            match audio::AudioTrack::load(std::path::Path::new(self.audio_path.to_str()), TIMESTEP) {
+                Ok(track) => {
+                    self.audio = Some(track);
+                    self.audio_position = 0.0;
+                    self.audio_playing = false;
+                    self.modulator.reset();
+                }
+                Err(e) => self.message = Some(e),
+            }
+        }
+        if let Some(track) = &self.audio {
+            ui.same_line(0.0);
+            let label = if self.audio_playing { im_str!("Pause##audio") } else { im_str!("Play##audio") };
+            if ui.button(label, [0.0, 0.0]) {
+                self.audio_playing = !self.audio_playing;
+                if !self.audio_playing {
+                    self.params.mark_all_dirty();
+                }
+            }
+            ui.same_line(0.0);
+            if ui.button(im_str!("Rewind##audio"), [0.0, 0.0]) {
+                self.audio_position = 0.0;
+                self.modulator.reset();
+            }
+            ui.text(format!(
+                "{:.1}s / {:.1}s",
+                self.audio_position * track.frame_time,
+                track.duration(),
+            ));
+            let features = track.features((self.audio_position as usize).saturating_sub(1)).copied().unwrap_or_default();
+            for feature in Feature::ALL.iter() {
+                imgui::ProgressBar::new(feature.get(&features))
+                    .size([120.0, 0.0])
+                    .overlay_text(&imgui::ImString::new(feature.label()))
+                    .build(ui);
+            }
+        }
+
+        let paths = params::field_paths();
+        let mut remove = None;
+        for (i, mapping) in self.params.audio_mappings.iter_mut().enumerate() {
+            let id = ui.push_id(i as i32);
+            ui.separator();
+            let mut feature = Feature::ALL.iter().position(|f| *f == mapping.feature).unwrap_or(0);
+            ui.set_next_item_width(80.0);
+            if imgui::ComboBox::new(im_str!("##feature")).build_simple(ui, &mut feature, &Feature::ALL, &|f: &Feature| {
+                std::borrow::Cow::from(imgui::ImString::new(f.label()))
+            }) {
+                mapping.feature = Feature::ALL[feature];
+            }
+            ui.same_line(0.0);
+            ui.text(format!("-> {}", mapping.path));
+            ui.same_line(0.0);
+            if ui.small_button(im_str!("Remove")) {
+                remove = Some(i);
+            }
+            imgui::Slider::new(im_str!("Gain"))
+                .range(-2.0..=2.0)
+                .build(ui, &mut mapping.gain);
+            imgui::Slider::new(im_str!("Smoothing"))
+                .range(0.0..=0.99)
+                .build(ui, &mut mapping.smoothing);
+            if let Some(level) = self.modulator.levels().get(i) {
+                imgui::ProgressBar::new(*level).size([-1.0, 4.0]).build(ui);
+            }
+            id.pop(ui);
+        }
+        if let Some(i) = remove {
+            self.params.audio_mappings.remove(i);
+            self.modulator.reset();
+            self.params.mark_all_dirty();
+        }
+
+        ui.separator();
+        self.mapping_path = self.mapping_path.min(paths.len() - 1);
+        imgui::ComboBox::new(im_str!("Field##audio")).build_simple(ui, &mut self.mapping_path, &paths, &|p: &String| {
+            std::borrow::Cow::from(imgui::ImString::new(p))
+        });
+        ui.same_line(0.0);
+        if ui.button(im_str!("Add mapping"), [0.0, 0.0]) {
+            let mapping = modulation::AudioMapping::new(Feature::Rms, &paths[self.mapping_path]);
+            self.params.audio_mappings.push(mapping);
+        }
+    }
+
+    fn timeline_ui(&mut self, ui: &imgui::Ui) {
+        use imgui::im_str;
+
+        let playhead = &mut self.playhead;
+        let timeline = &mut self.params.timeline;
+        let label = if playhead.playing { im_str!("Pause") } else { im_str!("Play") };
+        if ui.button(label, [0.0, 0.0]) {
+            if !playhead.playing && playhead.time >= timeline.duration {
+                playhead.seek(0.0);
+            }
+            playhead.playing = !playhead.playing;
+        }
+        ui.same_line(0.0);
+        if ui.button(im_str!("Rewind"), [0.0, 0.0]) {
+            playhead.seek(0.0);
+        }
+        ui.same_line(0.0);
+        ui.checkbox(im_str!("Loop##timeline"), &mut timeline.looping);
+
+        let mut time = playhead.time;
+        if imgui::Slider::new(im_str!("Time (s)"))
+            .range(0.0..=timeline.duration)
+            .build(ui, &mut time) {
+            playhead.seek(time);
+        }
+        imgui::Slider::new(im_str!("Duration (s)##timeline"))
+            .range(1.0..=600.0)
+            .build(ui, &mut timeline.duration);
+
+        let paths = params::field_paths();
+        let mut changed = false;
+        let mut remove_track = None;
+        for (t, track) in timeline.tracks.iter_mut().enumerate() {
+            let id = ui.push_id(t as i32);
+            ui.separator();
+            ui.text(&track.path);
+            ui.same_line(0.0);
+            if ui.small_button(im_str!("Key")) {
+                if let Some((_, value)) = self.params.params[self.params.current].field(&track.path) {
+                    track.insert(timeline::Keyframe { time: playhead.time, value, easing: Default::default() });
+                    changed = true;
+                }
+            }
+            ui.same_line(0.0);
+            if ui.small_button(im_str!("Remove")) {
+                remove_track = Some(t);
+            }
+
+            let mut remove_key = None;
+            let mut reorder = false;
+            for (k, key) in track.keyframes.iter_mut().enumerate() {
+                let id = ui.push_id(k as i32);
+                ui.set_next_item_width(80.0);
+                reorder |= imgui::Drag::new(im_str!("##time"))
+                    .range(0.0..=timeline.duration)
+                    .speed(0.05)
+                    .display_format(im_str!("%.2fs"))
+                    .build(ui, &mut key.time);
+                ui.same_line(0.0);
+                ui.set_next_item_width(100.0);
+                let mut easing = Easing::ALL.iter().position(|e| *e == key.easing).unwrap_or(1);
+                if imgui::ComboBox::new(im_str!("##easing")).build_simple(ui, &mut easing, &Easing::ALL, &|e: &Easing| {
+                    std::borrow::Cow::from(imgui::ImString::new(e.label()))
+                }) {
+                    key.easing = Easing::ALL[easing];
+                    changed = true;
+                }
+                ui.same_line(0.0);
+                ui.text(format_value(key.value));
+                ui.same_line(0.0);
+                if ui.small_button(im_str!("x")) {
+                    remove_key = Some(k);
+                }
+                id.pop(ui);
+            }
+            if reorder {
+                track.sort();
+                changed = true;
+            }
+            if let Some(k) = remove_key {
+                track.keyframes.remove(k);
+                changed = true;
+            }
+            id.pop(ui);
+        }
+        if let Some(t) = remove_track {
+            timeline.tracks.remove(t);
+            // restore the preset value of the field that was animated
+            self.params.mark_all_dirty();
+        }
+
+        ui.separator();
+        self.track_path = self.track_path.min(paths.len() - 1);
+        imgui::ComboBox::new(im_str!("Field")).build_simple(ui, &mut self.track_path, &paths, &|p: &String| {
+            std::borrow::Cow::from(imgui::ImString::new(p))
         });
+        ui.same_line(0.0);
+        let path = &paths[self.track_path];
+        let exists = self.params.timeline.tracks.iter().any(|t| &t.path == path);
+        if ui.button(im_str!("Add track"), [0.0, 0.0]) && !exists {
+            self.params.timeline.tracks.push(timeline::Track::new(path));
+        }
+        if changed {
+            self.playhead.changed = true;
+        }
+    }
+}
+
+fn format_value(value: ParamValue) -> String {
+    match value {
+        ParamValue::Float(v) => format!("{:.3}", v),
+        ParamValue::Count(v) => v.to_string(),
+        ParamValue::Color([r, g, b]) => format!("({:.2}, {:.2}, {:.2})", r, g, b),
     }
 }
 
+fn preset_combo(ui: &imgui::Ui, label: &imgui::ImStr, index: &mut usize, presets: &[params::Params]) -> bool {
+    imgui::ComboBox::new(label)
+        .flags(imgui::ComboBoxFlags::empty())
+        .build_simple(ui, index, presets, &|p: &params::Params| {
+            let name = if p.builtin { format!("{} (built-in)", p.name) } else { p.name.clone() };
+            std::borrow::Cow::from(imgui::ImString::new(name))
+        })
+}
+
+/// Builds one widget per reflected field, returns true if any value changed.
+/// `clicked` is set to the path of a clicked field
+fn edit_section(ui: &imgui::Ui, section: &mut dyn Reflect, max_particles: u32, clicked: &mut Option<String>) -> bool {
+    let mut changed = false;
+    ui.text(&imgui::ImString::new(section.title()));
+    for (index, field) in section.fields().iter().enumerate() {
+        let label = imgui::ImString::new(field.label);
+        let edited = match (field.kind, section.get(index)) {
+            (ParamKind::Float { min, max }, ParamValue::Float(mut v)) => {
+                imgui::Slider::new(&label)
+                    .range(min..=max)
+                    .build(ui, &mut v)
+                    && section.set(index, ParamValue::Float(v))
+            }
+            (ParamKind::Count, ParamValue::Count(mut v)) => {
+                imgui::Slider::new(&label)
+                    .range(0u32..=max_particles-1)
+                    .build(ui, &mut v)
+                    && section.set(index, ParamValue::Count(v))
+            }
+            (ParamKind::Color, ParamValue::Color(mut v)) => {
+                imgui::ColorPicker::new(&label, &mut v)
+                    .input_mode(imgui::ColorEditInputMode::Rgb)
+                    .mode(imgui::ColorPickerMode::HueWheel)
+                    .build(ui)
+                    && section.set(index, ParamValue::Color(v))
+            }
+            (kind, value) => {
+                log::warn!("{}: value {:?} does not match kind {:?}", field.name, value, kind);
+                false
+            }
+        };
+        if ui.is_item_clicked(imgui::MouseButton::Left) {
+            *clicked = Some(format!("{}.{}", section.key(), field.name));
+        }
+        changed |= edited;
+    }
+    changed
+}
+
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    std::process::exit(2);
+}
+
 fn main() {
-    framework::run::<MoldSim>("Mold sim");
+    let args = match cli::Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => exit_with_error(&format!("{}\n\n{}", e, cli::USAGE)),
+    };
+    if args.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+
+    let config_path = args.config.clone().unwrap_or_else(config::default_config_path);
+    let mut config = config::AppConfig::load(&config_path).unwrap_or_else(|e| exit_with_error(&e));
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(&config.log_level)).init();
+    log::info!("Logger started");
+    let state_path = config::default_state_path();
+    let state = config::State::load(&state_path).unwrap_or_else(|e| {
+        log::warn!("{}, ignoring it", e);
+        config::State::default()
+    });
+    config.apply_state(&state);
+
+    let params_path = args.params.clone().unwrap_or_else(params::default_user_path);
+    let mut params = ParamManager::load_merged(&params_path).unwrap_or_else(|e| {
+        eprintln!("{}, using built-in presets only", e);
+        ParamManager::builtin()
+    });
+
+    if args.list_presets {
+        for p in &params.params {
+            println!("{}{}", p.name, if p.builtin { " (built-in)" } else { "" });
+        }
+        return;
+    }
+
+    if let Some(name) = &args.preset {
+        match params.params.iter().position(|p| &p.name == name) {
+            Some(index) => params.current = index,
+            None => exit_with_error(&format!("unknown preset \"{}\", see --list-presets", name)),
+        }
+    } else if let Some(name) = &config.default_preset {
+        match params.params.iter().position(|p| &p.name == name) {
+            Some(index) => params.current = index,
+            None => log::warn!("{}: unknown default_preset \"{}\"", config_path.display(), name),
+        }
+    }
+    if let Some(transition) = args.transition {
+        params.global.transition_time = transition.max(0.0);
+    }
+    for name in &args.playlist {
+        if !params.params.iter().any(|p| &p.name == name) {
+            exit_with_error(&format!("unknown playlist preset \"{}\", see --list-presets", name));
+        }
+    }
+    // played in place of the saved playlist, which Save must not overwrite with it
+    let playlist = if args.playlist.is_empty() {
+        None
+    } else {
+        let entry = PlaylistEntry {
+            duration: args.interval.unwrap_or(60.0),
+            transition: params.global.transition_time,
+            respawn: args.reset,
+            ..PlaylistEntry::new("")
+        };
+        Some(Playlist {
+            entries: args.playlist.iter().map(|name| PlaylistEntry { name: name.clone(), ..entry.clone() }).collect(),
+            // batch renders play the list once
+            looping: args.render.is_none(),
+        })
+    };
+    let play = args.play || playlist.is_some();
+    if play {
+        // start on the first entry instead of morphing into it
+        let played = playlist.as_ref().unwrap_or(&params.playlist);
+        let first = played.entries.first().map(|e| e.name.clone());
+        if let Some(index) = first.and_then(|name| params.params.iter().position(|p| p.name == name)) {
+            params.current = index;
+        }
+    }
+
+    if let Some(particles) = args.particles {
+        // particle buffers are sized from max_particles, the slider stops one short of it
+        params.global.max_particles = params.global.max_particles.max(particles + 1);
+        params.global.num_particles = Some(particles);
+    }
+
+    let mut display = config.to_display();
+    if args.fullscreen && display.window_mode == WindowMode::Windowed {
+        display.window_mode = WindowMode::Borderless;
+    }
+    if args.monitor.is_some() {
+        display.monitor = args.monitor.clone();
+    }
+
+    let settings = framework::Settings {
+        title: "Mold sim".to_owned(),
+        size: match args.resolution {
+            Some((width, height)) => winit::dpi::PhysicalSize::new(width, height).into(),
+            None => winit::dpi::LogicalSize::new(config.window.width, config.window.height).into(),
+        },
+        backend: args.backend.unwrap_or(wgpu::BackendBit::PRIMARY),
+        power_preference: args.power.unwrap_or_default(),
+        display,
+        show_ui: !args.no_ui,
+        font: config.ui.font.clone(),
+        ui_scale: config.ui.scale,
+    };
+    let script = args.script.as_ref().map(|path| {
+        if !path.exists() {
+            exit_with_error(&format!("{} not found", path.display()));
+        }
+        script::Script::load(path)
+    });
+    let midi = if args.midi.is_some() || config.midi.enabled {
+        let port = args.midi.as_deref().or_else(|| config.midi.port.as_deref());
+        match midi::MidiInput::connect(port) {
+            Ok(input) => Some(input),
+            Err(e) => exit_with_error(&e),
+        }
+    } else {
+        None
+    };
+    let midi_playback = args.midi_replay.as_ref().map(|path| {
+        midi::Recording::load(path).map(midi::Playback::new).unwrap_or_else(|e| exit_with_error(&e))
+    });
+    let audio = args.audio.as_ref().map(|path| {
+        audio::AudioTrack::load(path, TIMESTEP).unwrap_or_else(|e| exit_with_error(&e))
+    });
+    let played = playlist.as_ref().unwrap_or(&params.playlist);
+    let default_frames = if play && !played.looping {
+        Some((played.total_duration() / TIMESTEP).ceil() as u32)
+    } else {
+        audio.as_ref().map(|a| a.frames.len() as u32)
+    };
+    let osc = if args.osc.is_some() || config.osc.enabled {
+        let port = args.osc.unwrap_or(config.osc.port);
+        match osc::OscServer::bind((config.osc.address.as_str(), port)) {
+            Ok(server) => Some(server),
+            Err(e) => exit_with_error(&format!("can't listen for OSC on {}:{}: {}", config.osc.address, port, e)),
+        }
+    } else {
+        None
+    };
+    let api = if args.api.is_some() || config.api.enabled {
+        let port = args.api.unwrap_or(config.api.port);
+        match api::ApiServer::bind((config.api.address.as_str(), port)) {
+            Ok(server) => Some(server),
+            Err(e) => exit_with_error(&format!("can't serve the API on {}:{}: {}", config.api.address, port, e)),
+        }
+    } else {
+        None
+    };
+    let options = SimOptions { params, params_path, seed: args.seed, config, state, state_path, playlist, play, animate: args.animate, audio, script, midi, midi_playback, osc, api, fixed_step: args.render.is_some() };
+
+    if let Some(output) = &args.render {
+        let frames = args.frames
+            .or(default_frames)
+            .unwrap_or_else(|| exit_with_error("--render needs --frames unless a playlist is played once or --audio is given"));
+        if let Err(e) = framework::render_headless::<MoldSim>(&settings, options, frames, output) {
+            exit_with_error(&e);
+        }
+        return;
+    }
+    framework::run::<MoldSim>(settings, options);
 }
@@ -0,0 +1,215 @@
+use std::path::Path;
+#[cfg(feature = "midi")]
+use std::sync::mpsc::{self, Receiver};
+use serde::{Deserialize, Serialize};
+use crate::params::{ParamKind, ParamValue, Params};
+use crate::remote::Command;
+
+/// A control change message, the only kind of MIDI message used
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ControlChange {
+    /// 1 to 16
+    pub channel: u8,
+    pub controller: u8,
+    /// 0 to 127
+    pub value: u8,
+}
+
+impl ControlChange {
+    /// Decodes a raw message, anything but a control change gives None
+    pub fn parse(bytes: &[u8]) -> Option<ControlChange> {
+        match *bytes {
+            [status, controller, value, ..] if status & 0xF0 == 0xB0 => Some(ControlChange {
+                channel: (status & 0x0F) + 1,
+                controller: controller & 0x7F,
+                value: value & 0x7F,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Binds a controller to a field of the current preset
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MidiBinding {
+    pub channel: u8,
+    pub controller: u8,
+    /// `section.field` path
+    pub path: String,
+}
+
+impl MidiBinding {
+    pub fn matches(&self, event: &ControlChange) -> bool {
+        self.channel == event.channel && self.controller == event.controller
+    }
+}
+
+/// Whether a field can be bound, colors have no single range to scale to
+pub fn bindable(path: &str) -> bool {
+    match Params::default().field(path) {
+        Some((field, _)) => field.kind != ParamKind::Color,
+        None => false,
+    }
+}
+
+/// Binds the controller of `event` to `path`, replacing earlier bindings of either
+pub fn bind(bindings: &mut Vec<MidiBinding>, event: &ControlChange, path: &str) {
+    bindings.retain(|b| !b.matches(event) && b.path != path);
+    bindings.push(MidiBinding { channel: event.channel, controller: event.controller, path: path.to_owned() });
+    log::info!("Bound CC {} on channel {} to {}", event.controller, event.channel, path);
+}
+
+/// Commands setting the fields bound to the controller of `event`
+pub fn commands(event: &ControlChange, bindings: &[MidiBinding], max_particles: u32) -> Vec<Command> {
+    bindings.iter()
+        .filter(|b| b.matches(event))
+        .filter_map(|b| {
+            let (field, _) = Params::default().field(&b.path)?;
+            let value = scale(field.kind, event.value, max_particles)?;
+            Some(Command::Set { path: b.path.clone(), value })
+        })
+        .collect()
+}
+
+/// Maps 0..=127 onto the range of a field
+pub fn scale(kind: ParamKind, value: u8, max_particles: u32) -> Option<ParamValue> {
+    let t = value.min(127) as f32 / 127.0;
+    match kind {
+        ParamKind::Float { min, max } => Some(ParamValue::Float(min + t * (max - min))),
+        ParamKind::Count => Some(ParamValue::Count((t * max_particles.saturating_sub(1) as f32).round() as u32)),
+        ParamKind::Color => None,
+    }
+}
+
+/// Receives control changes from a MIDI input port on the driver's thread
+#[cfg(feature = "midi")]
+pub struct MidiInput {
+    pub port: String,
+    receiver: Receiver<ControlChange>,
+    /// Closes the port when dropped
+    _connection: midir::MidiInputConnection<()>,
+}
+
+#[cfg(feature = "midi")]
+impl MidiInput {
+    pub fn ports() -> Result<Vec<String>, String> {
+        let input = midir::MidiInput::new("moldsim").map_err(|e| e.to_string())?;
+        Ok(input.ports().iter().filter_map(|port| input.port_name(port).ok()).collect())
+    }
+
+    /// Connects to the first port whose name contains `name`, or to the first port
+    pub fn connect(name: Option<&str>) -> Result<MidiInput, String> {
+        let input = midir::MidiInput::new("moldsim").map_err(|e| e.to_string())?;
+        let ports = input.ports();
+        let port = ports.iter()
+            .find(|port| name.map_or(true, |name| input.port_name(port).map_or(false, |n| n.contains(name))))
+            .cloned()
+            .ok_or_else(|| match name {
+                Some(name) => format!("no MIDI input port matching \"{}\"", name),
+                None => "no MIDI input ports".to_owned(),
+            })?;
+        let port_name = input.port_name(&port).map_err(|e| e.to_string())?;
+
+        let (sender, receiver) = mpsc::channel();
+        let connection = input
+            .connect(&port, "moldsim-in", move |_, bytes, _| {
+                if let Some(event) = ControlChange::parse(bytes) {
+                    let _ = sender.send(event);
+                }
+            }, ())
+            .map_err(|e| e.to_string())?;
+
+        log::info!("MIDI input connected to {}", port_name);
+        Ok(MidiInput { port: port_name, receiver, _connection: connection })
+    }
+
+    /// Control changes received since the last call
+    pub fn poll(&self) -> impl Iterator<Item = ControlChange> + '_ {
+        self.receiver.try_iter()
+    }
+}
+
+/// Timed control changes, stored as `seconds channel controller value` lines
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Recording {
+    pub events: Vec<(f32, ControlChange)>,
+}
+
+impl Recording {
+    pub fn load(path: &Path) -> Result<Recording, String> {
+        let contents = std::fs::read_to_string(path).map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+        Recording::parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        std::fs::write(path, self.to_string()).map_err(|e| format!("Error writing {}: {}", path.display(), e))
+    }
+
+    /// Reads one event per line, blank lines and `#` comments are skipped
+    pub fn parse(contents: &str) -> Result<Recording, String> {
+        let mut events = Vec::new();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = || format!("line {}: expected \"seconds channel controller value\"", number + 1);
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (time, channel, controller, value) = match fields.as_slice() {
+                [time, channel, controller, value] => (time, channel, controller, value),
+                _ => return Err(error()),
+            };
+            let time: f32 = time.parse().map_err(|_| error())?;
+            let event = ControlChange {
+                channel: channel.parse().ok().filter(|c| (1..=16).contains(c)).ok_or_else(error)?,
+                controller: controller.parse().ok().filter(|c| *c < 128).ok_or_else(error)?,
+                value: value.parse().ok().filter(|v| *v < 128).ok_or_else(error)?,
+            };
+            events.push((time, event));
+        }
+        events.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        Ok(Recording { events })
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.events.last().map_or(0.0, |(time, _)| *time)
+    }
+}
+
+impl std::fmt::Display for Recording {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "# seconds channel controller value")?;
+        for (time, event) in &self.events {
+            writeln!(f, "{:.4} {} {} {}", time, event.channel, event.controller, event.value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Replays a recording in simulation time, a stand-in for a controller
+#[derive(Debug, Clone, Default)]
+pub struct Playback {
+    pub recording: Recording,
+    pub time: f32,
+    next: usize,
+}
+
+impl Playback {
+    pub fn new(recording: Recording) -> Playback {
+        Playback { recording, time: 0.0, next: 0 }
+    }
+
+    /// Advances by `dt` seconds and returns the events that came due
+    pub fn tick(&mut self, dt: f32) -> Vec<ControlChange> {
+        self.time += dt;
+        let start = self.next;
+        while self.recording.events.get(self.next).map_or(false, |(time, _)| *time <= self.time) {
+            self.next += 1;
+        }
+        self.recording.events[start..self.next].iter().map(|(_, event)| *event).collect()
+    }
+
+    pub fn finished(&self) -> bool {
+        self.next >= self.recording.events.len()
+    }
+}
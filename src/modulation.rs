@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+use crate::params::{ParamKind, ParamValue, Params};
+
+/// Audio features of one simulation frame, each normalized to [0, 1] over the track
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Features {
+    pub rms: f32,
+    pub bass: f32,
+    pub mid: f32,
+    pub treble: f32,
+    pub onset: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Feature {
+    Rms,
+    Bass,
+    Mid,
+    Treble,
+    Onset,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 5] = [Feature::Rms, Feature::Bass, Feature::Mid, Feature::Treble, Feature::Onset];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Feature::Rms => "RMS",
+            Feature::Bass => "Bass",
+            Feature::Mid => "Mid",
+            Feature::Treble => "Treble",
+            Feature::Onset => "Onset",
+        }
+    }
+
+    pub fn get(self, features: &Features) -> f32 {
+        match self {
+            Feature::Rms => features.rms,
+            Feature::Bass => features.bass,
+            Feature::Mid => features.mid,
+            Feature::Treble => features.treble,
+            Feature::Onset => features.onset,
+        }
+    }
+}
+
+/// Adds an audio feature to a field, saved with the user presets
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AudioMapping {
+    pub feature: Feature,
+    /// `section.field` path, e.g. `particle.trail_power`
+    pub path: String,
+    /// Fraction of the field range added at full feature level, negative values subtract
+    pub gain: f32,
+    /// 0 follows the feature instantly, values towards 1 respond more slowly
+    pub smoothing: f32,
+}
+
+impl AudioMapping {
+    pub fn new(feature: Feature, path: &str) -> Self {
+        Self {
+            feature,
+            path: path.to_owned(),
+            gain: 0.5,
+            smoothing: 0.8,
+        }
+    }
+}
+
+/// Applies mappings frame by frame, keeping the smoothed level of each one
+#[derive(Debug, Default)]
+pub struct Modulator {
+    levels: Vec<f32>,
+}
+
+impl Modulator {
+    pub fn reset(&mut self) {
+        self.levels.clear();
+    }
+
+    /// Smoothed level of each mapping after the last `apply`
+    pub fn levels(&self) -> &[f32] {
+        &self.levels
+    }
+
+    /// Offsets the mapped fields of `params` by the smoothed features,
+    /// returns the sections that were touched, indexed like `Params::sections`
+    pub fn apply(&mut self, mappings: &[AudioMapping], features: &Features, params: &mut Params, max_particles: u32) -> [bool; 4] {
+        self.levels.resize(mappings.len(), 0.0);
+        let mut touched = [false; 4];
+        for (mapping, level) in mappings.iter().zip(self.levels.iter_mut()) {
+            let smoothing = mapping.smoothing.max(0.0).min(0.99);
+            *level = *level * smoothing + mapping.feature.get(features) * (1.0 - smoothing);
+
+            let (field, value) = match params.field(&mapping.path) {
+                Some(found) => found,
+                None => continue,
+            };
+            let amount = mapping.gain * *level;
+            let value = match (field.kind, value) {
+                (ParamKind::Float { min, max }, ParamValue::Float(v)) => {
+                    ParamValue::Float((v + amount * (max - min)).max(min).min(max))
+                }
+                (ParamKind::Count, ParamValue::Count(v)) => {
+                    let top = max_particles.saturating_sub(1) as f32;
+                    ParamValue::Count((v as f32 + amount * top).max(0.0).min(top) as u32)
+                }
+                (ParamKind::Color, ParamValue::Color(c)) => {
+                    let scale = (1.0 + amount).max(0.0);
+                    ParamValue::Color([(c[0] * scale).min(1.0), (c[1] * scale).min(1.0), (c[2] * scale).min(1.0)])
+                }
+                (_, value) => value,
+            };
+            if params.set_field(&mapping.path, value) {
+                let section = params.sections().iter().position(|s| mapping.path.starts_with(&format!("{}.", s.key())));
+                if let Some(section) = section {
+                    touched[section] = true;
+                }
+            }
+        }
+        touched
+    }
+}
@@ -0,0 +1,109 @@
+use crate::params::{ParamValue, Params};
+
+/// Blends every reflected field of two presets, colors are mixed in Oklab
+/// so intermediate hues don't go muddy. The result keeps the name of `b`.
+pub fn lerp_params(a: &Params, b: &Params, t: f32) -> Params {
+    let mut out = b.clone();
+    for (i, section) in out.sections_mut().iter_mut().enumerate() {
+        let (from, to) = (a.sections()[i], b.sections()[i]);
+        for index in 0..section.fields().len() {
+            section.set(index, lerp_value(from.get(index), to.get(index), t));
+        }
+    }
+    out
+}
+
+pub fn lerp_value(a: ParamValue, b: ParamValue, t: f32) -> ParamValue {
+    match (a, b) {
+        (ParamValue::Float(a), ParamValue::Float(b)) => ParamValue::Float(lerp(a, b, t)),
+        (ParamValue::Count(a), ParamValue::Count(b)) => {
+            ParamValue::Count(lerp(a as f32, b as f32, t).round() as u32)
+        }
+        (ParamValue::Color(a), ParamValue::Color(b)) => {
+            let (a, b) = (srgb_to_oklab(a), srgb_to_oklab(b));
+            ParamValue::Color(oklab_to_srgb([lerp(a[0], b[0], t), lerp(a[1], b[1], t), lerp(a[2], b[2], t)]))
+        }
+        (_, b) => b,
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+}
+
+pub fn srgb_to_oklab(c: [f32; 3]) -> [f32; 3] {
+    let [r, g, b] = [srgb_to_linear(c[0]), srgb_to_linear(c[1]), srgb_to_linear(c[2])];
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+    [
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    ]
+}
+
+pub fn oklab_to_srgb(c: [f32; 3]) -> [f32; 3] {
+    let l = (c[0] + 0.3963377774 * c[1] + 0.2158037573 * c[2]).powi(3);
+    let m = (c[0] - 0.1055613458 * c[1] - 0.0638541728 * c[2]).powi(3);
+    let s = (c[0] - 0.0894841775 * c[1] - 1.2914855480 * c[2]).powi(3);
+    let rgb = [
+        4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+        -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+        -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+    ];
+    [
+        linear_to_srgb(rgb[0]).max(0.0).min(1.0),
+        linear_to_srgb(rgb[1]).max(0.0).min(1.0),
+        linear_to_srgb(rgb[2]).max(0.0).min(1.0),
+    ]
+}
+
+/// Transition from a snapshot of the previously shown values to the current preset
+#[derive(Debug, Clone)]
+pub struct Morph {
+    pub from: Params,
+    pub elapsed: f32,
+    pub duration: f32,
+}
+
+impl Morph {
+    pub fn new(from: Params, duration: f32) -> Self {
+        Self { from, elapsed: 0.0, duration }
+    }
+
+    /// Advances the transition, returns false once it has finished
+    pub fn advance(&mut self, dt: f32) -> bool {
+        self.elapsed += dt;
+        self.elapsed < self.duration
+    }
+
+    /// Eased progress in 0..=1
+    pub fn progress(&self) -> f32 {
+        let t = (self.elapsed / self.duration).max(0.0).min(1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+/// Manual blend between two presets, overrides the current preset while enabled
+#[derive(Debug, Clone, Copy)]
+pub struct Crossfade {
+    pub enabled: bool,
+    pub a: usize,
+    pub b: usize,
+    pub amount: f32,
+}
+
+impl Default for Crossfade {
+    fn default() -> Self {
+        Self { enabled: false, a: 0, b: 0, amount: 0.0 }
+    }
+}
@@ -0,0 +1,140 @@
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::time::Duration;
+use rosc::{OscMessage, OscPacket, OscType};
+use crate::params::{ParamKind, ParamValue, Params};
+use crate::remote::Command;
+
+/// Address prefix of every message, e.g. `/moldsim/particle/speed`
+pub const PREFIX: &str = "/moldsim";
+
+/// Listens for OSC messages on a background thread
+pub struct OscServer {
+    addr: SocketAddr,
+    receiver: Receiver<Command>,
+    stop: Arc<AtomicBool>,
+}
+
+impl OscServer {
+    pub fn bind(addr: impl ToSocketAddrs) -> std::io::Result<OscServer> {
+        let socket = UdpSocket::bind(addr)?;
+        // wake up now and then to notice when the server is dropped
+        socket.set_read_timeout(Some(Duration::from_millis(200)))?;
+        let addr = socket.local_addr()?;
+        let (sender, receiver) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+
+        std::thread::Builder::new().name("osc".to_owned()).spawn(move || {
+            let mut buf = [0u8; rosc::decoder::MTU];
+            while !stopped.load(Ordering::Relaxed) {
+                let size = match socket.recv_from(&mut buf) {
+                    Ok((size, _)) => size,
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock || e.kind() == std::io::ErrorKind::TimedOut => continue,
+                    Err(e) => {
+                        log::error!("OSC: {}", e);
+                        return;
+                    }
+                };
+                let packet = match rosc::decoder::decode(&buf[..size]) {
+                    Ok(packet) => packet,
+                    Err(e) => {
+                        log::warn!("OSC: invalid packet: {:?}", e);
+                        continue;
+                    }
+                };
+                for command in commands(packet) {
+                    match command {
+                        Ok(command) => {
+                            if sender.send(command).is_err() {
+                                return;
+                            }
+                        }
+                        Err(e) => log::warn!("OSC: {}", e),
+                    }
+                }
+            }
+        })?;
+
+        log::info!("OSC listening on {}", addr);
+        Ok(OscServer { addr, receiver, stop })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Commands received since the last call
+    pub fn poll(&self) -> impl Iterator<Item = Command> + '_ {
+        self.receiver.try_iter()
+    }
+}
+
+impl Drop for OscServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Flattens bundles into the commands of their messages
+fn commands(packet: OscPacket) -> Vec<Result<Command, String>> {
+    match packet {
+        OscPacket::Message(message) => vec![command(&message)],
+        OscPacket::Bundle(bundle) => bundle.content.into_iter().flat_map(commands).collect(),
+    }
+}
+
+/// Maps a message onto a command, fields are converted using their metadata
+pub fn command(message: &OscMessage) -> Result<Command, String> {
+    let path = message.addr
+        .strip_prefix(PREFIX)
+        .and_then(|rest| rest.strip_prefix('/'))
+        .ok_or_else(|| format!("{}: expected an address starting with {}/", message.addr, PREFIX))?;
+    let args = &message.args;
+
+    match path {
+        "reset" => Ok(Command::Reset),
+        "preset" => match args.first() {
+            Some(OscType::String(name)) => Ok(Command::Preset(name.clone())),
+            Some(arg) => number(arg)
+                .filter(|n| *n >= 0.0)
+                .map(|n| Command::PresetIndex(n as usize))
+                .ok_or_else(|| format!("{}: expected a preset name or index", message.addr)),
+            None => Err(format!("{}: missing preset name", message.addr)),
+        },
+        _ => {
+            let path = path.replacen('/', ".", 1);
+            let (field, _) = Params::default()
+                .field(&path)
+                .ok_or_else(|| format!("{}: unknown parameter", message.addr))?;
+            let value = match field.kind {
+                ParamKind::Float { .. } => args.first().and_then(number).map(ParamValue::Float),
+                ParamKind::Count => args.first().and_then(number).map(|n| ParamValue::Count(n.max(0.0).round() as u32)),
+                ParamKind::Color => color(args).map(ParamValue::Color),
+            };
+            let value = value.ok_or_else(|| format!("{}: unexpected arguments {:?}", message.addr, args))?;
+            Ok(Command::Set { path, value })
+        }
+    }
+}
+
+fn number(arg: &OscType) -> Option<f32> {
+    match *arg {
+        OscType::Float(v) => Some(v),
+        OscType::Double(v) => Some(v as f32),
+        OscType::Int(v) => Some(v as f32),
+        OscType::Long(v) => Some(v as f32),
+        _ => None,
+    }
+}
+
+/// Three numbers in [0, 1] or an OSC color
+fn color(args: &[OscType]) -> Option<[f32; 3]> {
+    match args {
+        [OscType::Color(c), ..] => Some([c.red as f32 / 255.0, c.green as f32 / 255.0, c.blue as f32 / 255.0]),
+        [r, g, b, ..] => Some([number(r)?, number(g)?, number(b)?]),
+        _ => None,
+    }
+}
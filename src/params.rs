@@ -1,11 +1,33 @@
 use serde::{Deserialize, Serialize};
-use crate::uniform::Uniform;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use crate::history::UndoStack;
+use crate::morph::{lerp_params, Crossfade, Morph};
+use crate::playlist::Playlist;
+use crate::modulation::AudioMapping;
+use crate::timeline::Timeline;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ParamManager {
     pub current: usize,
     pub params: Vec<Params>,
     pub global: GlobalParams,
+    #[serde(default)]
+    pub playlist: Playlist,
+    #[serde(default)]
+    pub timeline: Timeline,
+    #[serde(default)]
+    pub audio_mappings: Vec<AudioMapping>,
+    /// Sections whose uniform needs uploading, indexed like `Params::sections`
+    #[serde(skip)]
+    dirty: [bool; 4],
+    #[serde(skip)]
+    morph: Option<Morph>,
+    #[serde(skip)]
+    pub crossfade: Crossfade,
+    /// Undo stacks, indexed like `params`
+    #[serde(skip)]
+    history: Vec<UndoStack>,
 }
 
 impl ParamManager {
@@ -18,79 +40,656 @@ impl ParamManager {
     }
 
     pub fn new(&mut self) {
-        self.params.push(self.current().clone());
-        self.current = self.params.len() - 1;
-        self.current_mut().name = format!("Custom {}", self.current);
+        let mut params = self.current().clone();
+        params.name = format!("Custom {}", self.params.len());
+        self.add(params);
+    }
+
+    /// Appends a preset under a unique name and selects it
+    pub fn add(&mut self, mut params: Params) {
+        params.builtin = false;
+        params.name = self.unique_name(&params.name, None);
+        self.params.push(params);
+        self.select(self.params.len() - 1);
+    }
+
+    /// Inserts a copy of the current preset after it
+    pub fn duplicate(&mut self) {
+        let index = self.insert_copy();
+        self.select(index);
+    }
+
+    /// The current preset for editing. Built-ins are never edited, the first
+    /// edit copies them and selects the copy
+    pub fn current_editable(&mut self) -> &mut Params {
+        if self.current().builtin {
+            self.current = self.insert_copy();
+            log::info!("Editing built-in preset as \"{}\"", self.current().name);
+        }
+        self.current_mut()
+    }
+
+    /// Inserts a user copy of the current preset after it and returns its index
+    fn insert_copy(&mut self) -> usize {
+        let mut params = self.current().clone();
+        params.builtin = false;
+        params.name = self.unique_name(&format!("{} copy", params.name), None);
+        self.sync_history();
+        // the caller edits the copy right away, undo goes back to the unedited copy
+        self.history.insert(self.current + 1, UndoStack::with_committed(params.clone()));
+        self.params.insert(self.current + 1, params);
+        let current = self.current;
+        self.shift_crossfade(|i| if i > current { i + 1 } else { i });
+        current + 1
+    }
+
+    /// Removes the current preset, built-ins and the last remaining preset can't be deleted
+    pub fn delete(&mut self) {
+        if self.params.len() <= 1 || self.current().builtin {
+            return;
+        }
+        let index = self.current;
+        self.sync_history();
+        self.params.remove(index);
+        self.history.remove(index);
+        self.shift_crossfade(|i| if i > index { i - 1 } else if i == index { 0 } else { i });
+        self.current = index.min(self.params.len() - 1);
+        self.morph = None;
+        self.mark_all_dirty();
+    }
+
+    /// Moves the current preset by `offset` positions, keeping it selected
+    pub fn move_by(&mut self, offset: isize) {
+        let from = self.current;
+        let to = from as isize + offset;
+        if to < 0 || to as usize >= self.params.len() {
+            return;
+        }
+        let to = to as usize;
+        self.sync_history();
+        self.params.swap(from, to);
+        self.history.swap(from, to);
+        self.shift_crossfade(|i| if i == from { to } else if i == to { from } else { i });
+        self.current = to;
+    }
+
+    /// Renames the current preset, empty or taken names are made unique
+    pub fn rename(&mut self, name: &str) {
+        if self.current().builtin {
+            return;
+        }
+        let name = self.unique_name(name, Some(self.current));
+        let old = std::mem::replace(&mut self.current_mut().name, name.clone());
+        for entry in self.playlist.entries.iter_mut().filter(|e| e.name == old) {
+            entry.name = name.clone();
+        }
+    }
+
+    /// Returns `name` trimmed, or with a numeric suffix if another preset uses it
+    pub fn unique_name(&self, name: &str, except: Option<usize>) -> String {
+        let base = match name.trim() {
+            "" => "Untitled",
+            trimmed => trimmed,
+        };
+        let taken = |candidate: &str| {
+            self.params.iter().enumerate().any(|(i, p)| Some(i) != except && p.name == candidate)
+        };
+        if !taken(base) {
+            return base.to_owned();
+        }
+        (2..).map(|n| format!("{} {}", base, n)).find(|c| !taken(c)).unwrap()
+    }
+
+    /// Reloads only the current preset, matched by name, from the built-ins
+    /// or from the user presets at `path`
+    pub fn revert(&mut self, path: &Path) -> Result<(), String> {
+        let saved = match self.current().builtin {
+            true => ParamManager::builtin(),
+            false => ParamManager::load(path)?,
+        };
+        let name = &self.current().name;
+        let params = saved.params.into_iter()
+            .find(|p| &p.name == name)
+            .ok_or_else(|| format!("\"{}\" is not saved in {}", name, path.display()))?;
+        *self.current_mut() = params;
+        self.mark_all_dirty();
+        Ok(())
+    }
+
+    /// Re-reads the user presets at `path` and applies them live, keeping the
+    /// selected preset by name along with undo history and blend state
+    pub fn reload(&mut self, path: &Path) -> Result<(), String> {
+        let loaded = ParamManager::load_merged(path)?;
+
+        let name = &self.current().name;
+        let current = loaded.params.iter()
+            .position(|p| &p.name == name)
+            .unwrap_or_else(|| self.current.min(loaded.params.len() - 1));
+
+        self.sync_history();
+        let mut history: std::collections::HashMap<String, UndoStack> = self.params.iter()
+            .map(|p| p.name.clone())
+            .zip(self.history.drain(..))
+            .collect();
+        self.history = loaded.params.iter()
+            .map(|p| history.remove(&p.name).unwrap_or_default())
+            .collect();
+
+        // particle buffers are sized once at startup
+        let max_particles = self.global.max_particles;
+        let num_particles = self.global.num_particles;
+        self.params = loaded.params;
+        self.global = loaded.global;
+        self.global.max_particles = max_particles;
+        self.global.num_particles = num_particles;
+        self.playlist = loaded.playlist;
+        self.timeline = loaded.timeline;
+        self.audio_mappings = loaded.audio_mappings;
+        self.current = current;
+
+        let last = self.params.len() - 1;
+        self.shift_crossfade(|i| i.min(last));
+        self.mark_all_dirty();
+        Ok(())
+    }
+
+    fn sync_history(&mut self) {
+        self.history.resize_with(self.params.len(), UndoStack::default);
+    }
+
+    fn shift_crossfade(&mut self, map: impl Fn(usize) -> usize) {
+        self.crossfade.a = map(self.crossfade.a);
+        self.crossfade.b = map(self.crossfade.b);
+    }
+
+    /// Copies the values of `params` into the current preset, keeping its name
+    pub fn apply(&mut self, params: &Params) {
+        let name = std::mem::replace(&mut self.current_editable().name, String::new());
+        *self.current_mut() = Params { name, builtin: false, ..params.clone() };
+        self.mark_all_dirty();
+    }
+
+    /// Switches to another preset, morphing into it over `global.transition_time`
+    pub fn select(&mut self, index: usize) {
+        self.transition_to(index, self.global.transition_time);
+    }
+
+    /// Switches to another preset, morphing into it over `duration` seconds
+    pub fn transition_to(&mut self, index: usize, duration: f32) {
+        if index == self.current || index >= self.params.len() {
+            return;
+        }
+        if duration > 0.0 {
+            let from = self.effective().into_owned();
+            self.morph = Some(Morph::new(from, duration));
+        }
+        self.current = index;
+        self.mark_all_dirty();
+    }
+
+    /// Advances an active transition by `dt` seconds
+    pub fn tick(&mut self, dt: f32) {
+        if let Some(morph) = self.morph.as_mut() {
+            if !morph.advance(dt) {
+                self.morph = None;
+            }
+            self.mark_all_dirty();
+        }
+    }
+
+    pub fn is_morphing(&self) -> bool {
+        self.morph.is_some()
+    }
+
+    /// Values to upload this frame: the crossfade, a running transition or the current preset,
+    /// with the global particle count override
+    pub fn effective(&self) -> Cow<Params> {
+        let fade = &self.crossfade;
+        let mut params = if fade.enabled && fade.a < self.params.len() && fade.b < self.params.len() {
+            Cow::Owned(lerp_params(&self.params[fade.a], &self.params[fade.b], fade.amount))
+        } else {
+            match &self.morph {
+                Some(morph) => Cow::Owned(lerp_params(&morph.from, self.current(), morph.progress())),
+                None => Cow::Borrowed(self.current()),
+            }
+        };
+        if let Some(num_particles) = self.global.num_particles {
+            if params.particle.num_particles != num_particles {
+                params.to_mut().particle.num_particles = num_particles;
+            }
+        }
+        params
+    }
+
+    fn history_mut(&mut self) -> &mut UndoStack {
+        self.sync_history();
+        &mut self.history[self.current]
+    }
+
+    /// Records edits of the current preset, pass `editing` while a widget is held
+    pub fn observe_edits(&mut self, editing: bool) {
+        let current = self.current().clone();
+        self.history_mut().observe(&current, editing);
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.history.get(self.current).map_or(false, |h| h.can_undo())
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.history.get(self.current).map_or(false, |h| h.can_redo())
+    }
+
+    pub fn undo(&mut self) {
+        let current = self.current().clone();
+        if let Some(previous) = self.history_mut().undo(&current) {
+            *self.current_mut() = previous;
+            self.mark_all_dirty();
+        }
+    }
+
+    pub fn redo(&mut self) {
+        let current = self.current().clone();
+        if let Some(next) = self.history_mut().redo(&current) {
+            *self.current_mut() = next;
+            self.mark_all_dirty();
+        }
+    }
+
+    /// Sets a field of the current preset by its `section.field` path, clamped
+    /// to the field range, and marks its section for upload
+    pub fn set_path(&mut self, path: &str, value: ParamValue) -> bool {
+        let field = match self.current().field(path) {
+            Some((field, _)) => field,
+            None => return false,
+        };
+        let value = match field.clamp(value, self.global.max_particles) {
+            Some(value) => value,
+            None => return false,
+        };
+        if self.current().field(path).map(|(_, old)| old) == Some(value) {
+            return true;
+        }
+        if !self.current_editable().set_field(path, value) {
+            return false;
+        }
+        let key = path.split('.').next().unwrap_or_default();
+        if let Some(section) = self.current().sections().iter().position(|s| s.key() == key) {
+            self.mark_dirty(section);
+        }
+        true
+    }
+
+    /// Selects the preset called `name`, returns false if there is none
+    pub fn select_by_name(&mut self, name: &str) -> bool {
+        match self.params.iter().position(|p| p.name == name) {
+            Some(index) => {
+                self.select(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn mark_dirty(&mut self, section: usize) {
+        self.dirty[section] = true;
+    }
+
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty = [true; 4];
+    }
+
+    /// Returns the dirty sections and clears them
+    pub fn take_dirty(&mut self) -> [bool; 4] {
+        std::mem::take(&mut self.dirty)
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
 pub struct Params {
     pub name: String,
+    /// Shipped with the binary, never renamed, deleted or saved
+    #[serde(skip)]
+    pub builtin: bool,
     pub particle: ParticleParams,
     pub decay: DecayParams,
     pub diffuse: DiffuseParams,
     pub render: RenderParams,
 }
+
+impl Params {
+    /// All reflected sections in uniform upload order
+    pub fn sections(&self) -> [&dyn Reflect; 4] {
+        [&self.particle, &self.decay, &self.diffuse, &self.render]
+    }
+
+    pub fn sections_mut(&mut self) -> [&mut dyn Reflect; 4] {
+        [&mut self.particle, &mut self.decay, &mut self.diffuse, &mut self.render]
+    }
+
+    /// Looks up a field by its `section.field` path, e.g. `particle.speed`
+    pub fn field(&self, path: &str) -> Option<(ParamField, ParamValue)> {
+        let (key, name) = split_path(path)?;
+        let section = self.sections().iter().find(|s| s.key() == key).copied()?;
+        let index = section.fields().iter().position(|f| f.name == name)?;
+        Some((section.fields()[index], section.get(index)))
+    }
+
+    /// Sets a field by its `section.field` path, returns false if the path
+    /// is unknown or the value has the wrong kind
+    pub fn set_field(&mut self, path: &str, value: ParamValue) -> bool {
+        let (key, name) = match split_path(path) {
+            Some(split) => split,
+            None => return false,
+        };
+        for section in self.sections_mut().iter_mut() {
+            if section.key() != key {
+                continue;
+            }
+            return match section.fields().iter().position(|f| f.name == name) {
+                Some(index) => section.set(index, value),
+                None => false,
+            };
+        }
+        false
+    }
+
+    /// Limits every field to its range, e.g. for presets sent over the network.
+    /// Fails on the first value that isn't a finite number
+    pub fn clamped(mut self, max_particles: u32) -> Result<Params, String> {
+        for section in self.sections_mut().iter_mut() {
+            for (index, field) in section.fields().iter().enumerate() {
+                let value = section.get(index);
+                let finite = match value {
+                    ParamValue::Float(v) => v.is_finite(),
+                    ParamValue::Count(_) => true,
+                    ParamValue::Color(c) => c.iter().all(|v| v.is_finite()),
+                };
+                if !finite {
+                    return Err(format!("{}.{} is not a finite number", section.key(), field.name));
+                }
+                if let Some(value) = field.clamp(value, max_particles) {
+                    section.set(index, value);
+                }
+            }
+        }
+        Ok(self)
+    }
+}
+
+/// `section.field` paths of every reflected field, in upload order
+pub fn field_paths() -> Vec<String> {
+    let params = Params::default();
+    params.sections().iter()
+        .flat_map(|s| s.fields().iter().map(move |f| format!("{}.{}", s.key(), f.name)))
+        .collect()
+}
+
+fn split_path(path: &str) -> Option<(&str, &str)> {
+    let mut parts = path.splitn(2, '.');
+    Some((parts.next()?, parts.next()?))
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GlobalParams {
     pub post_enabled: bool,
     pub max_particles: u32,
+    /// Seconds spent morphing into a newly selected preset
+    #[serde(default)]
+    pub transition_time: f32,
+    /// Particle count used by every preset instead of their own, from `--particles`
+    #[serde(skip)]
+    pub num_particles: Option<u32>,
+}
+
+/// How a reflected parameter is bounded and edited
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParamKind {
+    /// Float within an inclusive range
+    Float { min: f32, max: f32 },
+    /// Particle count, bounded by `GlobalParams::max_particles`
+    Count,
+    /// RGB color with components in 0..=1
+    Color,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamValue {
+    Float(f32),
+    Count(u32),
+    Color([f32; 3]),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ParamField {
+    pub name: &'static str,
+    pub label: &'static str,
+    pub kind: ParamKind,
+}
+
+impl ParamField {
+    /// Limits `value` to the field range, None if it has the wrong kind
+    pub fn clamp(&self, value: ParamValue, max_particles: u32) -> Option<ParamValue> {
+        match (self.kind, value) {
+            (ParamKind::Float { min, max }, ParamValue::Float(v)) => Some(ParamValue::Float(v.max(min).min(max))),
+            (ParamKind::Count, ParamValue::Count(v)) => Some(ParamValue::Count(v.min(max_particles.saturating_sub(1)))),
+            (ParamKind::Color, ParamValue::Color(c)) => Some(ParamValue::Color([
+                c[0].max(0.0).min(1.0),
+                c[1].max(0.0).min(1.0),
+                c[2].max(0.0).min(1.0),
+            ])),
+            _ => None,
+        }
+    }
+}
+
+/// Field level access to a parameter section, generated by `reflected_params!`
+pub trait Reflect {
+    /// Serialized name of the section inside `Params`
+    fn key(&self) -> &'static str;
+    /// Heading shown in the UI
+    fn title(&self) -> &'static str;
+    fn fields(&self) -> &'static [ParamField];
+    fn get(&self, index: usize) -> ParamValue;
+    fn set(&mut self, index: usize, value: ParamValue) -> bool;
+}
+
+/// Rust types that can back a reflected field
+pub trait ParamType {
+    fn to_value(&self) -> ParamValue;
+    fn set_value(&mut self, value: ParamValue) -> bool;
+}
+
+impl ParamType for f32 {
+    fn to_value(&self) -> ParamValue {
+        ParamValue::Float(*self)
+    }
+
+    fn set_value(&mut self, value: ParamValue) -> bool {
+        match value {
+            ParamValue::Float(v) => { *self = v; true }
+            _ => false,
+        }
+    }
+}
+
+impl ParamType for u32 {
+    fn to_value(&self) -> ParamValue {
+        ParamValue::Count(*self)
+    }
+
+    fn set_value(&mut self, value: ParamValue) -> bool {
+        match value {
+            ParamValue::Count(v) => { *self = v; true }
+            _ => false,
+        }
+    }
+}
+
+impl ParamType for [f32; 3] {
+    fn to_value(&self) -> ParamValue {
+        ParamValue::Color(*self)
+    }
+
+    fn set_value(&mut self, value: ParamValue) -> bool {
+        match value {
+            ParamValue::Color(v) => { *self = v; true }
+            _ => false,
+        }
+    }
+}
+
+/// Declares a parameter section together with its reflection data, so a new
+/// field only has to be added here (and in the matching WGSL struct)
+macro_rules! reflected_params {
+    (
+        $(#[$meta:meta])*
+        pub struct $name:ident($key:literal, $title:literal) {
+            $($field:ident: $ty:ty => $label:literal, $kind:expr;)*
+        }
+    ) => {
+        $(#[$meta])*
+        pub struct $name {
+            $(pub $field: $ty,)*
+        }
+
+        impl Reflect for $name {
+            fn key(&self) -> &'static str {
+                $key
+            }
+
+            fn title(&self) -> &'static str {
+                $title
+            }
+
+            fn fields(&self) -> &'static [ParamField] {
+                const FIELDS: &[ParamField] = &[
+                    $(ParamField { name: stringify!($field), label: $label, kind: $kind },)*
+                ];
+                FIELDS
+            }
+
+            fn get(&self, index: usize) -> ParamValue {
+                let slots = [$(&self.$field as &dyn ParamType),*];
+                slots[index].to_value()
+            }
+
+            fn set(&mut self, index: usize, value: ParamValue) -> bool {
+                let mut slots = [$(&mut self.$field as &mut dyn ParamType),*];
+                match slots.get_mut(index) {
+                    Some(slot) => slot.set_value(value),
+                    None => false,
+                }
+            }
+        }
+    };
 }
 
-#[repr(C, packed)]
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
-pub struct ParticleParams {
-    pub trail_power: f32,
-    pub speed: f32,
-    pub sensor_angle: f32,
-    pub sensor_distance: f32,
-    pub turn_speed: f32,
-    pub num_particles: u32,
+reflected_params! {
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+    pub struct ParticleParams("particle", "Particle Compute") {
+        trail_power: f32 => "Trail Power", ParamKind::Float { min: 0.0, max: 64.0 };
+        speed: f32 => "Speed", ParamKind::Float { min: 0.0, max: 15.0 };
+        sensor_angle: f32 => "Sensor Angle", ParamKind::Float { min: 0.0, max: 1.5 };
+        sensor_distance: f32 => "Sensor Distance", ParamKind::Float { min: 0.0, max: 0.01 };
+        turn_speed: f32 => "Turn Speed", ParamKind::Float { min: 0.0, max: 3.14 };
+        num_particles: u32 => "Num Particles", ParamKind::Count;
+    }
 }
 
-#[repr(C, packed)]
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
-pub struct DecayParams {
-    pub decay_rate: f32,
+reflected_params! {
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+    pub struct DecayParams("decay", "Decay Compute") {
+        decay_rate: f32 => "Decay Factor", ParamKind::Float { min: 0.5, max: 1.0 };
+    }
+}
 
+reflected_params! {
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+    pub struct DiffuseParams("diffuse", "Diffuse Compute") {
+        diffuse_amount: f32 => "Diffuse Amount", ParamKind::Float { min: 0.0, max: 1.0 };
+    }
 }
-#[repr(C, packed)]
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
-pub struct DiffuseParams {
-    pub diffuse_amount: f32,
+
+reflected_params! {
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+    pub struct RenderParams("render", "Render") {
+        color_1: [f32; 3] => "Color 1", ParamKind::Color;
+        color_2: [f32; 3] => "Color 2", ParamKind::Color;
+        color_pow: f32 => "Color Power", ParamKind::Float { min: 0.2, max: 1.0 };
+        cutoff: f32 => "Cutoff", ParamKind::Float { min: 0.0, max: 1.0 };
+    }
 }
 
-#[repr(C, packed)]
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
-pub struct RenderParams {
-    pub color_1: [f32; 3],
-    pub color_2: [f32; 3],
-    pub color_pow: f32,
-    pub cutoff: f32,
+const BUILTIN_PARAMS: &str = include_str!("../resources/params.json");
+
+/// What gets written to the user presets file
+#[derive(Serialize)]
+struct SavedParams<'a> {
+    current: usize,
+    params: Vec<&'a Params>,
+    global: &'a GlobalParams,
+    playlist: &'a Playlist,
+    timeline: &'a Timeline,
+    audio_mappings: &'a [AudioMapping],
 }
 
-impl Uniform for ParticleParams {}
-impl Uniform for DecayParams {}
-impl Uniform for DiffuseParams {}
-impl Uniform for RenderParams {}
+/// User presets live in the per-user config directory
+pub fn default_user_path() -> PathBuf {
+    dirs::config_dir()
+        .map(|dir| dir.join("moldsim").join("params.json"))
+        .unwrap_or_else(|| PathBuf::from("params.json"))
+}
 
 impl ParamManager {
-    pub fn from_json(path: &str) -> ParamManager {
-        use std::io::prelude::*;
-        use std::fs::File;
+    /// Presets compiled into the binary
+    pub fn builtin() -> ParamManager {
+        let mut manager: ParamManager = serde_json::from_str(BUILTIN_PARAMS).unwrap();
+        manager.params.iter_mut().for_each(|p| p.builtin = true);
+        manager
+    }
+
+    /// Built-in presets followed by the user presets at `path`, if it exists
+    pub fn load_merged(path: &Path) -> Result<ParamManager, String> {
+        let mut manager = ParamManager::builtin();
+        if !path.exists() {
+            return Ok(manager);
+        }
+        let user = ParamManager::load(path)?;
+        for mut params in user.params {
+            params.name = manager.unique_name(&params.name, None);
+            manager.params.push(params);
+        }
+        manager.global = user.global;
+        manager.playlist = user.playlist;
+        manager.timeline = user.timeline;
+        manager.audio_mappings = user.audio_mappings;
+        if user.current < manager.params.len() {
+            manager.current = user.current;
+        }
+        Ok(manager)
+    }
 
-        let mut file = File::open(path).unwrap();
-        let mut buf = Vec::new();
-        file.read_to_end(&mut buf).unwrap();
-        
-        serde_json::from_slice(&buf).unwrap()
+    pub fn load(path: &Path) -> Result<ParamManager, String> {
+        let buf = std::fs::read(path).map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+        serde_json::from_slice(&buf).map_err(|e| format!("Error parsing {}: {}", path.display(), e))
     }
 
-    pub fn save(&self, path: &str) {
-        match std::fs::write(path, serde_json::to_vec_pretty(&self).unwrap().as_slice()) {
-            Err(e) => log::error!("Error saving params to file: {}", e),
-            _ => ()
+    /// Writes the user presets and global settings, built-ins are left out
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let saved = SavedParams {
+            current: self.current,
+            params: self.params.iter().filter(|p| !p.builtin).collect(),
+            global: &self.global,
+            playlist: &self.playlist,
+            timeline: &self.timeline,
+            audio_mappings: &self.audio_mappings,
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Error creating {}: {}", dir.display(), e))?;
         }
+        std::fs::write(path, serde_json::to_vec_pretty(&saved).unwrap().as_slice())
+            .map_err(|e| format!("Error saving params to {}: {}", path.display(), e))
     }
 }
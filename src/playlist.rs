@@ -0,0 +1,161 @@
+use serde::{Deserialize, Serialize};
+use crate::params::ParamManager;
+
+/// Shortest entry duration, keeps a zero length entry from switching every frame
+const MIN_DURATION: f32 = 0.1;
+
+/// Ordered presets shown one after another, saved with the user presets
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Playlist {
+    pub entries: Vec<PlaylistEntry>,
+    /// Start over after the last entry instead of stopping
+    #[serde(default)]
+    pub looping: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PlaylistEntry {
+    /// Preset name, looked up when the entry starts
+    pub name: String,
+    /// Seconds the preset is shown, including the transition into it
+    pub duration: f32,
+    /// Seconds spent morphing from the previous preset
+    pub transition: f32,
+    /// Respawn particles and clear trails when the entry starts
+    #[serde(default)]
+    pub respawn: bool,
+}
+
+impl PlaylistEntry {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            duration: 60.0,
+            transition: 1.5,
+            respawn: false,
+        }
+    }
+}
+
+impl Playlist {
+    /// Seconds until the last entry ends
+    pub fn total_duration(&self) -> f32 {
+        self.entries.iter().map(|e| e.duration.max(MIN_DURATION)).sum()
+    }
+}
+
+/// Drives `ParamManager::current` through the playlist in simulated time
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    playing: bool,
+    position: usize,
+    elapsed: f32,
+    /// Played instead of `ParamManager::playlist` and never saved, e.g. from `--playlist`
+    temporary: Option<Playlist>,
+}
+
+impl Scheduler {
+    /// Plays `playlist` instead of the one saved with the presets
+    pub fn with_playlist(playlist: Playlist) -> Self {
+        Self {
+            temporary: Some(playlist),
+            ..Self::default()
+        }
+    }
+
+    /// The playlist being played
+    pub fn playlist<'a>(&'a self, params: &'a ParamManager) -> &'a Playlist {
+        self.temporary.as_ref().unwrap_or(&params.playlist)
+    }
+
+    pub fn is_temporary(&self) -> bool {
+        self.temporary.is_some()
+    }
+
+    /// Stops and goes back to the playlist saved with the presets
+    pub fn clear_temporary(&mut self) {
+        self.playing = false;
+        self.temporary = None;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Index of the entry being shown
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Seconds left of the entry being shown
+    pub fn remaining(&self, playlist: &Playlist) -> f32 {
+        playlist.entries.get(self.position)
+            .map_or(0.0, |e| (e.duration.max(MIN_DURATION) - self.elapsed).max(0.0))
+    }
+
+    /// Starts playing from entry `position`, returns true when the particles should respawn
+    pub fn play(&mut self, position: usize, params: &mut ParamManager) -> bool {
+        if position >= self.playlist(params).entries.len() {
+            self.playing = false;
+            return false;
+        }
+        self.playing = true;
+        self.play_from(position, params)
+    }
+
+    pub fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    /// Advances by `dt` seconds, returns true when the particles should respawn
+    pub fn tick(&mut self, dt: f32, params: &mut ParamManager) -> bool {
+        if !self.playing {
+            return false;
+        }
+        let playlist = self.playlist(params);
+        let (count, looping) = (playlist.entries.len(), playlist.looping);
+        let duration = match playlist.entries.get(self.position).map(|e| e.duration.max(MIN_DURATION)) {
+            Some(duration) => duration,
+            None => {
+                self.playing = false;
+                return false;
+            }
+        };
+        self.elapsed += dt;
+        if self.elapsed < duration {
+            return false;
+        }
+
+        let next = self.position + 1;
+        if next < count {
+            self.play_from(next, params)
+        } else if looping {
+            self.play_from(0, params)
+        } else {
+            log::info!("Playlist finished");
+            self.playing = false;
+            false
+        }
+    }
+
+    fn play_from(&mut self, position: usize, params: &mut ParamManager) -> bool {
+        self.position = position;
+        self.elapsed = 0.0;
+        let entry = self.playlist(params).entries[position].clone();
+        enter(&entry, params)
+    }
+}
+
+/// Switches to the preset of an entry, returns its respawn flag
+fn enter(entry: &PlaylistEntry, params: &mut ParamManager) -> bool {
+    match params.params.iter().position(|p| p.name == entry.name) {
+        Some(index) => {
+            params.transition_to(index, entry.transition);
+            entry.respawn
+        }
+        None => {
+            log::warn!("Playlist: preset \"{}\" does not exist", entry.name);
+            false
+        }
+    }
+}
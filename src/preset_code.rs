@@ -0,0 +1,114 @@
+use crate::params::{ParamKind, ParamValue, Params};
+
+// Layout of a decoded code:
+//   version: u8, name length: u8, name: utf-8,
+//   every reflected field in declaration order
+//     float: u16 normalized over the field range, count: u32, color: 3 x u8,
+//   crc32 of all preceding bytes: u32
+// All integers are little endian, the whole thing is url-safe base64.
+
+const VERSION: u8 = 1;
+const MAX_NAME_LEN: usize = 64;
+
+pub fn encode(params: &Params) -> String {
+    let mut name = params.name.as_str();
+    while name.len() > MAX_NAME_LEN {
+        name = &name[..name.char_indices().last().map_or(0, |(i, _)| i)];
+    }
+
+    let mut bytes = vec![VERSION, name.len() as u8];
+    bytes.extend_from_slice(name.as_bytes());
+    for section in params.sections().iter() {
+        for (index, field) in section.fields().iter().enumerate() {
+            match (field.kind, section.get(index)) {
+                (ParamKind::Float { min, max }, ParamValue::Float(v)) => {
+                    let t = ((v - min) / (max - min)).max(0.0).min(1.0);
+                    bytes.extend_from_slice(&((t * 65535.0).round() as u16).to_le_bytes());
+                }
+                (_, ParamValue::Count(v)) => bytes.extend_from_slice(&v.to_le_bytes()),
+                (_, ParamValue::Color(c)) => {
+                    bytes.extend(c.iter().map(|v| (v.max(0.0).min(1.0) * 255.0).round() as u8));
+                }
+                (_, ParamValue::Float(v)) => bytes.extend_from_slice(&v.to_le_bytes()),
+            }
+        }
+    }
+    let checksum = crc32fast::hash(&bytes);
+    bytes.extend_from_slice(&checksum.to_le_bytes());
+
+    base64::encode_config(&bytes, base64::URL_SAFE_NO_PAD)
+}
+
+/// Decodes a code, values are clamped to the field ranges and counts to below `max_particles`
+pub fn decode(code: &str, max_particles: u32) -> Result<Params, String> {
+    let bytes = base64::decode_config(code.trim(), base64::URL_SAFE_NO_PAD)
+        .map_err(|_| "Not a preset code (invalid base64)".to_owned())?;
+    if bytes.len() < 6 {
+        return Err("Preset code is too short".to_owned());
+    }
+
+    let (data, checksum) = bytes.split_at(bytes.len() - 4);
+    let mut expected = [0u8; 4];
+    expected.copy_from_slice(checksum);
+    if crc32fast::hash(data) != u32::from_le_bytes(expected) {
+        return Err("Checksum mismatch, the code is incomplete or mistyped".to_owned());
+    }
+
+    let mut reader = Reader { data, pos: 0 };
+    let version = reader.take(1)?[0];
+    if version != VERSION {
+        return Err(format!("Unsupported preset code version {}", version));
+    }
+    let name_len = reader.take(1)?[0] as usize;
+    let name = std::str::from_utf8(reader.take(name_len)?)
+        .map_err(|_| "Preset name is not valid UTF-8".to_owned())?;
+
+    let mut params = Params { name: name.to_owned(), ..Params::default() };
+    for section in params.sections_mut().iter_mut() {
+        for (index, field) in section.fields().iter().enumerate() {
+            let value = match (field.kind, section.get(index)) {
+                (ParamKind::Float { min, max }, ParamValue::Float(_)) => {
+                    let t = reader.u16()? as f32 / 65535.0;
+                    ParamValue::Float(min + (max - min) * t)
+                }
+                (_, ParamValue::Count(_)) => ParamValue::Count(reader.u32()?),
+                (_, ParamValue::Color(_)) => {
+                    let c = reader.take(3)?;
+                    ParamValue::Color([c[0] as f32 / 255.0, c[1] as f32 / 255.0, c[2] as f32 / 255.0])
+                }
+                (_, ParamValue::Float(_)) => ParamValue::Float(f32::from_bits(reader.u32()?)),
+            };
+            let value = field.clamp(value, max_particles)
+                .ok_or_else(|| format!("Preset code has an invalid value for {}", field.name))?;
+            section.set(index, value);
+        }
+    }
+    if reader.pos != data.len() {
+        return Err("Preset code has unexpected trailing data".to_owned());
+    }
+    Ok(params)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = self.data.get(self.pos..self.pos + len)
+            .ok_or_else(|| "Preset code ends unexpectedly".to_owned())?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let b = self.take(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+}
@@ -0,0 +1,126 @@
+use std::path::{Path, PathBuf};
+use crate::params::{ParamManager, Params};
+
+/// What to do when an imported preset has the name of an existing one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Conflict {
+    /// Keep both, the imported preset gets a numeric suffix
+    Rename,
+    /// Overwrite the existing preset
+    Replace,
+    /// Keep the existing preset
+    Skip,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Toml,
+}
+
+impl Format {
+    pub fn from_path(path: &Path) -> Option<Format> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "json" => Some(Format::Json),
+            "toml" => Some(Format::Toml),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Toml => "toml",
+        }
+    }
+}
+
+/// Writes a single preset. If `path` has no json/toml extension it is treated
+/// as a directory and the file is named after the preset.
+pub fn export(params: &Params, path: &Path, format: Format) -> Result<PathBuf, String> {
+    let path = match Format::from_path(path) {
+        Some(_) => path.to_owned(),
+        None => {
+            std::fs::create_dir_all(path).map_err(|e| format!("Error creating {}: {}", path.display(), e))?;
+            path.join(format!("{}.{}", file_stem(&params.name), format.extension()))
+        }
+    };
+    let contents = match format {
+        Format::Json => serde_json::to_string_pretty(params).map_err(|e| e.to_string())?,
+        Format::Toml => toml::to_string_pretty(params).map_err(|e| e.to_string())?,
+    };
+    std::fs::write(&path, contents).map_err(|e| format!("Error writing {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// Reads a preset file, the format is picked by extension
+pub fn read(path: &Path) -> Result<Params, String> {
+    let format = Format::from_path(path)
+        .ok_or_else(|| format!("{}: unknown preset format", path.display()))?;
+    let contents = std::fs::read_to_string(path).map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+    match format {
+        Format::Json => serde_json::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e)),
+        Format::Toml => toml::from_str(&contents).map_err(|e| format!("{}: {}", path.display(), e)),
+    }
+}
+
+/// Reads a preset file, or every preset file directly inside a directory.
+/// Files that fail to parse are reported next to the presets that loaded.
+pub fn read_all(path: &Path) -> (Vec<Params>, Vec<String>) {
+    if !path.is_dir() {
+        return match read(path) {
+            Ok(params) => (vec![params], Vec::new()),
+            Err(e) => (Vec::new(), vec![e]),
+        };
+    }
+
+    let mut files = match std::fs::read_dir(path) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.is_file() && Format::from_path(p).is_some())
+            .collect::<Vec<_>>(),
+        Err(e) => return (Vec::new(), vec![format!("Error reading {}: {}", path.display(), e)]),
+    };
+    files.sort();
+
+    let mut presets = Vec::new();
+    let mut errors = Vec::new();
+    for file in files {
+        match read(&file) {
+            Ok(params) => presets.push(params),
+            Err(e) => errors.push(e),
+        }
+    }
+    (presets, errors)
+}
+
+/// Replaces characters that are awkward in file names
+fn file_stem(name: &str) -> String {
+    let stem: String = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    if stem.is_empty() { "preset".to_owned() } else { stem }
+}
+
+/// Adds imported presets to `manager`, returns how many were added or replaced
+pub fn import(manager: &mut ParamManager, presets: Vec<Params>, conflict: Conflict) -> usize {
+    let mut count = 0;
+    for mut params in presets {
+        match manager.params.iter().position(|p| p.name == params.name) {
+            Some(_) if conflict == Conflict::Skip => continue,
+            Some(index) if conflict == Conflict::Replace && !manager.params[index].builtin => {
+                manager.params[index] = params;
+                if index == manager.current {
+                    manager.mark_all_dirty();
+                }
+            }
+            _ => {
+                params.name = manager.unique_name(&params.name, None);
+                manager.params.push(params);
+            }
+        }
+        count += 1;
+    }
+    count
+}
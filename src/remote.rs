@@ -0,0 +1,18 @@
+use crate::params::ParamValue;
+
+/// Changes requested by remote control sources, applied between frames
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Sets a field of the current preset by its `section.field` path
+    Set { path: String, value: ParamValue },
+    /// Selects a preset by name
+    Preset(String),
+    /// Selects a preset by position
+    PresetIndex(usize),
+    /// Respawns the particles and clears the trails
+    Reset,
+    /// Stops or resumes the simulation, the UI keeps running
+    Pause(bool),
+    /// Advances a paused simulation by one frame
+    Step,
+}
@@ -0,0 +1,174 @@
+use wgpu::util::DeviceExt;
+use crate::simulation::Simulation;
+
+/// Rectangle of the target the trails are stretched over, in pixels from the top left
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// How a `Renderer` is set up
+#[derive(Debug, Clone, Copy)]
+pub struct RendererDescriptor {
+    /// Format of the targets the renderer draws into
+    pub format: wgpu::TextureFormat,
+    /// Blends the trails over the target with alpha from their brightness instead
+    /// of covering the viewport, black is fully transparent
+    pub transparent: bool,
+}
+
+impl Default for RendererDescriptor {
+    fn default() -> Self {
+        Self {
+            format: wgpu::TextureFormat::Bgra8UnormSrgb,
+            transparent: false,
+        }
+    }
+}
+
+/// Draws the trails of a `Simulation` into any render pass, stretched over a
+/// viewport. Each target format needs its own renderer, any number of them can
+/// share one simulation
+pub struct Renderer {
+    pipeline: wgpu::RenderPipeline,
+    vertices_buffer: wgpu::Buffer,
+    format: wgpu::TextureFormat,
+    transparent: bool,
+}
+
+impl Renderer {
+    pub fn new(device: &wgpu::Device, simulation: &Simulation, desc: &RendererDescriptor) -> Renderer {
+        Renderer::with_layout(device, simulation.render_bind_group_layout(), desc, true)
+    }
+
+    /// Without `stretch` the trails are drawn one texel per pixel from the top
+    /// left of the target, the way `Simulation::draw` always has
+    pub(crate) fn with_layout(
+        device: &wgpu::Device,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        desc: &RendererDescriptor,
+        stretch: bool,
+    ) -> Renderer {
+        let draw_shader = device.create_shader_module(&wgpu::include_spirv!("../resources/spirv/draw.spv"));
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("render"),
+            bind_group_layouts: &[bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        // main_transparent outputs premultiplied alpha, it is always stretched
+        let (entry_point, blend) = if desc.transparent {
+            ("main_transparent", wgpu::BlendState {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                operation: wgpu::BlendOperation::Add,
+            })
+        } else if stretch {
+            ("main_stretched", wgpu::BlendState::REPLACE)
+        } else {
+            ("main", wgpu::BlendState::REPLACE)
+        };
+
+        log::info!("Creating render pipeline for {:?}...", desc.format);
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &draw_shader,
+                entry_point: "main",
+                buffers: &[
+                    wgpu::VertexBufferLayout {
+                        array_stride: 2 * 4,
+                        step_mode: wgpu::InputStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![0 => Float2],
+                    },
+                ],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &draw_shader,
+                entry_point,
+                targets: &[wgpu::ColorTargetState {
+                    format: desc.format,
+                    alpha_blend: blend,
+                    color_blend: blend,
+                    write_mask: wgpu::ColorWrite::ALL,
+                }],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+        });
+
+        let vertex_buffer_data = [-1.0f32, -1.0, 1.0, -1.0, 1.0, 1.0, -1.0, -1.0, 1.0, 1.0, -1.0, 1.0];
+        let vertices_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Vertex Buffer"),
+            contents: bytemuck::bytes_of(&vertex_buffer_data),
+            usage: wgpu::BufferUsage::VERTEX,
+        });
+
+        Renderer {
+            pipeline,
+            vertices_buffer,
+            format: desc.format,
+            transparent: desc.transparent,
+        }
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    pub fn transparent(&self) -> bool {
+        self.transparent
+    }
+
+    /// Draws the trails of the last step into `rpass`, stretched over `viewport`
+    /// or the whole target. The pass must target the descriptor's format.
+    /// The viewport, pipeline and bind group stay set on `rpass`, so draws
+    /// recorded after this one need to set their own viewport
+    pub fn render<'a>(&'a self, simulation: &'a Simulation, rpass: &mut wgpu::RenderPass<'a>, viewport: Option<Viewport>) {
+        if let Some(viewport) = viewport {
+            rpass.set_viewport(viewport.x, viewport.y, viewport.width, viewport.height, 0.0, 1.0);
+        }
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_vertex_buffer(0, self.vertices_buffer.slice(..));
+        rpass.set_bind_group(0, simulation.render_bind_group(), &[]);
+        rpass.draw(0..6, 0..1);
+    }
+
+    /// Records a render pass drawing into `view`. `clear` clears the whole
+    /// target first, None keeps what is already there
+    pub fn draw(
+        &self,
+        simulation: &Simulation,
+        command_encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        viewport: Option<Viewport>,
+        clear: Option<wgpu::Color>,
+    ) {
+        let color_attachments = [wgpu::RenderPassColorAttachmentDescriptor {
+            attachment: view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: clear.map_or(wgpu::LoadOp::Load, wgpu::LoadOp::Clear),
+                store: true,
+            },
+        }];
+        let render_pass_descriptor = wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &color_attachments,
+            depth_stencil_attachment: None,
+        };
+
+        command_encoder.push_debug_group("render to screen");
+        {
+            let mut rpass = command_encoder.begin_render_pass(&render_pass_descriptor);
+            self.render(simulation, &mut rpass, viewport);
+        }
+        command_encoder.pop_debug_group();
+    }
+}
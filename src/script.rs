@@ -0,0 +1,216 @@
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use rhai::{Array, Dynamic, Engine, ImmutableString, Map, RegisterFn, Scope, AST, FLOAT, INT};
+use crate::params::{ParamKind, ParamValue, Params};
+use crate::remote::Command;
+use crate::watch::FileWatcher;
+
+/// Function called every frame as `on_frame(t, params)`
+pub const HOOK: &str = "on_frame";
+
+/// Operations a single call may run before it is aborted, so a runaway loop can't freeze the app
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// A Rhai script automating the params, recompiled when the file changes
+pub struct Script {
+    pub path: PathBuf,
+    engine: Engine,
+    ast: Option<AST>,
+    watcher: FileWatcher,
+    /// Preset switches and resets requested by the script
+    commands: Rc<RefCell<Vec<Command>>>,
+    /// Seconds passed to the hook, advanced by `run`
+    pub time: f32,
+    compile_error: Option<String>,
+    run_error: Option<String>,
+    /// Sections the last call changed, uploaded again once the script leaves them alone
+    scripted: [bool; 4],
+}
+
+impl Script {
+    /// Compiles the script at `path`, errors are kept for `error` and the file is still watched
+    pub fn load(path: &Path) -> Script {
+        let commands = Rc::new(RefCell::new(Vec::new()));
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.on_print(|text| log::info!("script: {}", text));
+        engine.on_debug(|text| log::debug!("script: {}", text));
+
+        let queue = commands.clone();
+        engine.register_fn("preset", move |name: ImmutableString| {
+            queue.borrow_mut().push(Command::Preset(name.to_string()));
+        });
+        let queue = commands.clone();
+        engine.register_fn("preset", move |index: INT| {
+            queue.borrow_mut().push(Command::PresetIndex(index.max(0) as usize));
+        });
+        let queue = commands.clone();
+        engine.register_fn("reset", move || {
+            queue.borrow_mut().push(Command::Reset);
+        });
+
+        let mut script = Script {
+            path: path.to_owned(),
+            engine,
+            ast: None,
+            watcher: FileWatcher::new(path.to_owned()),
+            commands,
+            time: 0.0,
+            compile_error: None,
+            run_error: None,
+            scripted: [false; 4],
+        };
+        script.compile();
+        script
+    }
+
+    /// Recompiles the script if the file changed, a failed compile keeps the previous version running
+    pub fn poll(&mut self) -> bool {
+        if !self.watcher.poll() {
+            return false;
+        }
+        log::info!("Reloading {}", self.path.display());
+        self.compile();
+        true
+    }
+
+    fn compile(&mut self) {
+        let source = std::fs::read_to_string(&self.path)
+            .map_err(|e| format!("Error reading {}: {}", self.path.display(), e));
+        let ast = source.and_then(|source| {
+            self.engine.compile(&source).map_err(|e| format!("{}: {}", self.path.display(), e))
+        });
+        match ast {
+            Ok(ast) => {
+                self.ast = Some(ast);
+                self.compile_error = None;
+                self.run_error = None;
+            }
+            Err(e) => {
+                log::warn!("{}", e);
+                self.compile_error = Some(e);
+            }
+        }
+    }
+
+    /// The compile error, or the error of the last call
+    pub fn error(&self) -> Option<&str> {
+        self.compile_error.as_deref().or_else(|| self.run_error.as_deref())
+    }
+
+    /// Calls the hook with the params about to be uploaded and copies back
+    /// the params it returns, clamped to their ranges. Returns the sections to
+    /// upload: the changed ones and the ones changed by the previous call only
+    pub fn run(&mut self, dt: f32, params: &mut Params, max_particles: u32) -> [bool; 4] {
+        let changed = self.call(dt, params, max_particles);
+        let mut dirty = changed;
+        dirty.iter_mut().zip(self.scripted.iter()).for_each(|(d, s)| *d |= *s);
+        self.scripted = changed;
+        dirty
+    }
+
+    fn call(&mut self, dt: f32, params: &mut Params, max_particles: u32) -> [bool; 4] {
+        let ast = match &self.ast {
+            Some(ast) => ast,
+            None => return [false; 4],
+        };
+        // applied to a copy so a bad value leaves none of the others behind
+        let mut scripted = params.clone();
+        let result = self.engine
+            .call_fn::<_, Dynamic>(&mut Scope::new(), ast, HOOK, (self.time as FLOAT, to_map(params)))
+            .map_err(|e| e.to_string())
+            .and_then(|result| apply(result, &mut scripted, max_particles));
+        self.time += dt;
+
+        match result {
+            Ok(changed) => {
+                self.run_error = None;
+                *params = scripted;
+                changed
+            }
+            Err(e) => {
+                // the hook runs every frame, only log when the error changes
+                if self.run_error.as_ref() != Some(&e) {
+                    log::warn!("{}: {}", self.path.display(), e);
+                }
+                self.run_error = Some(e);
+                [false; 4]
+            }
+        }
+    }
+
+    /// Commands requested by the script since the last call
+    pub fn take_commands(&mut self) -> Vec<Command> {
+        std::mem::take(&mut *self.commands.borrow_mut())
+    }
+}
+
+/// `params` as an object map, e.g. `params.particle.speed` or `params.render.color_1[0]`
+fn to_map(params: &Params) -> Dynamic {
+    let mut map = Map::new();
+    map.insert("name".into(), Dynamic::from(params.name.clone()));
+    for section in params.sections().iter() {
+        let mut fields = Map::new();
+        for (index, field) in section.fields().iter().enumerate() {
+            fields.insert(field.name.into(), to_dynamic(section.get(index)));
+        }
+        map.insert(section.key().into(), Dynamic::from(fields));
+    }
+    Dynamic::from(map)
+}
+
+fn to_dynamic(value: ParamValue) -> Dynamic {
+    match value {
+        ParamValue::Float(v) => Dynamic::from(v as FLOAT),
+        ParamValue::Count(v) => Dynamic::from(v as INT),
+        ParamValue::Color(c) => Dynamic::from(c.iter().map(|v| Dynamic::from(*v as FLOAT)).collect::<Array>()),
+    }
+}
+
+/// Copies the fields of a returned map into `params`, missing fields are left alone
+fn apply(result: Dynamic, params: &mut Params, max_particles: u32) -> Result<[bool; 4], String> {
+    if result.is::<()>() {
+        return Ok([false; 4]);
+    }
+    let map = result
+        .try_cast::<Map>()
+        .ok_or_else(|| format!("{} should return the params or nothing", HOOK))?;
+
+    let mut changed = [false; 4];
+    for (section, changed) in params.sections_mut().iter_mut().zip(changed.iter_mut()) {
+        let fields = match map.get(section.key()).cloned().and_then(|fields| fields.try_cast::<Map>()) {
+            Some(fields) => fields,
+            None => continue,
+        };
+        for (index, field) in section.fields().iter().enumerate() {
+            let value = match fields.get(field.name) {
+                Some(value) => value,
+                None => continue,
+            };
+            let value = from_dynamic(field.kind, value)
+                .and_then(|value| field.clamp(value, max_particles))
+                .ok_or_else(|| format!("{}.{}: unexpected value {}", section.key(), field.name, value))?;
+            if section.get(index) != value {
+                section.set(index, value);
+                *changed = true;
+            }
+        }
+    }
+    Ok(changed)
+}
+
+fn from_dynamic(kind: ParamKind, value: &Dynamic) -> Option<ParamValue> {
+    match kind {
+        ParamKind::Float { .. } => number(value).map(|v| ParamValue::Float(v as f32)),
+        ParamKind::Count => number(value).map(|v| ParamValue::Count(v.max(0.0).round() as u32)),
+        ParamKind::Color => match value.clone().try_cast::<Array>()?.as_slice() {
+            [r, g, b] => Some(ParamValue::Color([number(r)? as f32, number(g)? as f32, number(b)? as f32])),
+            _ => None,
+        },
+    }
+}
+
+fn number(value: &Dynamic) -> Option<FLOAT> {
+    value.clone().try_cast::<FLOAT>().or_else(|| value.clone().try_cast::<INT>().map(|v| v as FLOAT))
+}
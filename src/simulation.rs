@@ -0,0 +1,715 @@
+use std::borrow::Cow;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use wgpu::util::DeviceExt;
+use crate::params::{ParamManager, Params};
+use crate::renderer::{Renderer, RendererDescriptor};
+use crate::uniform::Uniform;
+
+/// Particles per compute work group, matches the workgroup size of compute.wgsl
+const PARTICLES_PER_GROUP: u32 = 64;
+/// Largest particle buffer size, 16 bytes a particle fill the 128 MiB storage
+/// buffer binding every adapter supports
+pub const MAX_PARTICLES: u32 = 1 << 23;
+/// Size of the trail textures, the shaders assume it too
+pub const SCREEN_SIZE: (u32, u32) = (3200, 1800);
+// simulated seconds per frame, the compute shader deposits trails at the same rate
+pub const TIMESTEP: f32 = 1.0 / 144.0;
+
+struct SimBuffers {
+    particle_buffers: Vec<wgpu::Buffer>,
+    trail_textures: Vec<wgpu::Texture>,
+    particle_uniform: wgpu::Buffer,
+    decay_uniform: wgpu::Buffer,
+    diffuse_uniform: wgpu::Buffer,
+    render_uniform: wgpu::Buffer,
+}
+
+struct SimBindGroups {
+    particle_bind_groups: Vec<wgpu::BindGroup>,
+    trail_decay_bind_groups: Vec<wgpu::BindGroup>,
+    trail_diffuse_bind_groups: Vec<wgpu::BindGroup>,
+    render_bind_groups: Vec<wgpu::BindGroup>,
+}
+
+struct SimPipelines {
+    particle_bind_group_layout: wgpu::BindGroupLayout,
+    diffuse_bind_group_layout: wgpu::BindGroupLayout,
+    decay_bind_group_layout: wgpu::BindGroupLayout,
+    render_bind_group_layout: wgpu::BindGroupLayout,
+    particle_compute_pipeline: wgpu::ComputePipeline,
+    trail_decay_compute_pipeline: wgpu::ComputePipeline,
+    trail_diffuse_compute_pipeline: wgpu::ComputePipeline,
+}
+
+/// How a `Simulation` is set up
+#[derive(Debug, Clone)]
+pub struct SimulationDescriptor {
+    /// Format of the targets `draw` renders into, `Renderer` draws into others
+    pub format: wgpu::TextureFormat,
+    /// Size of the particle buffers, `num_particles` is limited to one less.
+    /// Limited to 1..=`MAX_PARTICLES`
+    pub max_particles: u32,
+    /// Seed for the particle positions, random if None
+    pub seed: Option<u64>,
+    /// Initial uniform values
+    pub params: Params,
+}
+
+impl Default for SimulationDescriptor {
+    /// The first built-in preset, drawn into an sRGB swapchain
+    fn default() -> Self {
+        let params = ParamManager::builtin();
+        Self {
+            format: wgpu::TextureFormat::Bgra8UnormSrgb,
+            max_particles: params.global.max_particles,
+            seed: None,
+            params: params.current().clone(),
+        }
+    }
+}
+
+/// The GPU side of the mold simulation: particle buffers, trail textures and
+/// the compute and render pipelines. Recording into caller owned encoders lets
+/// it run inside any wgpu app
+pub struct Simulation {
+    buffers: SimBuffers,
+    bind_groups: SimBindGroups,
+    pipelines: SimPipelines,
+    /// Draws into the descriptor's format for `draw`
+    renderer: Renderer,
+    /// Views of `buffers.trail_textures`
+    trail_views: Vec<wgpu::TextureView>,
+    particle_work_group_count: u32,
+    screen_work_group_count: (u32, u32),
+    max_particles: u32,
+    frame: usize,
+    rng: StdRng,
+    /// Runs the decay and diffuse passes after moving the particles
+    pub post_enabled: bool,
+}
+
+impl Simulation {
+    pub fn new(device: &wgpu::Device, desc: &SimulationDescriptor) -> Simulation {
+        let max_particles = desc.max_particles.max(1).min(MAX_PARTICLES);
+        if max_particles != desc.max_particles {
+            log::warn!("max_particles {} is out of range, using {}", desc.max_particles, max_particles);
+        }
+        let particle_buffer_size = wgpu::BufferSize::new(u64::from(max_particles) * 16);
+        let params = limit_particles(&desc.params, max_particles);
+
+        let (compute_shader, decay_shader, diffuse_shader) = (
+            device.create_shader_module(&wgpu::include_spirv!("../resources/spirv/compute.spv")),
+            device.create_shader_module(&wgpu::include_spirv!("../resources/spirv/decay.spv")),
+            device.create_shader_module(&wgpu::include_spirv!("../resources/spirv/diffuse.spv")),
+        );
+
+        let texture_size = wgpu::Extent3d {
+            width: SCREEN_SIZE.0,
+            height: SCREEN_SIZE.1,
+            depth: 1,
+        };
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            // the trail textures are R32Float, which samplers can't filter
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let pipelines = {
+
+            log::info!("Creating particle bind group...");
+            let particle_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStage::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: desc.params.particle.memsize(),
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStage::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: particle_buffer_size,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStage::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: particle_buffer_size,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStage::COMPUTE,
+                        ty: wgpu::BindingType::StorageTexture {
+                            access: wgpu::StorageTextureAccess::ReadOnly,
+                            format: wgpu::TextureFormat::R32Float,
+                            view_dimension: wgpu::TextureViewDimension::D2
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStage::COMPUTE,
+                        ty: wgpu::BindingType::StorageTexture {
+                            access: wgpu::StorageTextureAccess::WriteOnly,
+                            format: wgpu::TextureFormat::R32Float,
+                            view_dimension: wgpu::TextureViewDimension::D2
+                        },
+                        count: None,
+                    },
+                ],
+                label: None,
+            });
+
+            log::info!("Creating decay bind group...");
+            let decay_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStage::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: desc.params.decay.memsize(),
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStage::COMPUTE,
+                        ty: wgpu::BindingType::StorageTexture {
+                            access: wgpu::StorageTextureAccess::ReadOnly,
+                            format: wgpu::TextureFormat::R32Float,
+                            view_dimension: wgpu::TextureViewDimension::D2
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStage::COMPUTE,
+                        ty: wgpu::BindingType::StorageTexture {
+                            access: wgpu::StorageTextureAccess::WriteOnly,
+                            format: wgpu::TextureFormat::R32Float,
+                            view_dimension: wgpu::TextureViewDimension::D2
+                        },
+                        count: None,
+                    },
+                ],
+                label: None,
+            });
+
+            log::info!("Creating diffuse bind group...");
+            let diffuse_bind_group_layout = 
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStage::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: desc.params.diffuse.memsize(),
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStage::COMPUTE,
+                        ty: wgpu::BindingType::StorageTexture {
+                            access: wgpu::StorageTextureAccess::ReadOnly,
+                            format: wgpu::TextureFormat::R32Float,
+                            view_dimension: wgpu::TextureViewDimension::D2
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStage::COMPUTE,
+                        ty: wgpu::BindingType::StorageTexture {
+                            access: wgpu::StorageTextureAccess::WriteOnly,
+                            format: wgpu::TextureFormat::R32Float,
+                            view_dimension: wgpu::TextureViewDimension::D2
+                        },
+                        count: None,
+                    },
+                ],
+                label: None,
+            });
+
+            log::info!("Creating render bind group...");
+            let render_bind_group_layout = 
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: desc.params.render.memsize(),
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStage::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler {
+                            comparison: false,
+                            filtering: false,
+                        },
+                        count: None,
+                    },
+                ],
+                label: None,
+            });
+
+            log::info!("Creating particle pipeline layout...");
+            let particle_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("particle"),
+                bind_group_layouts: &[&particle_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+            log::info!("Creating decay pipeline layout...");
+            let decay_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("decay"),
+                bind_group_layouts: &[&decay_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+            log::info!("Creating diffuse pipeline layout...");
+            let diffuse_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("diffuse"),
+                bind_group_layouts: &[&diffuse_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+            log::info!("Creating particle pipeline...");
+            let particle_compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Particle compute pipeline"),
+                layout: Some(&particle_pipeline_layout),
+                module: &compute_shader,
+                entry_point: "main",
+            });
+    
+            log::info!("Creating decay pipeline...");
+            let trail_decay_compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Decay compute pipeline"),
+                layout: Some(&decay_pipeline_layout),
+                module: &decay_shader,
+                entry_point: "main",
+            });
+    
+            log::info!("Creating diffuse pipeline...");
+            let trail_diffuse_compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Diffuse compute pipeline"),
+                layout: Some(&diffuse_pipeline_layout),
+                module: &diffuse_shader,
+                entry_point: "main",
+            });
+
+            SimPipelines {
+                particle_bind_group_layout,
+                diffuse_bind_group_layout,
+                decay_bind_group_layout,
+                render_bind_group_layout,
+                particle_compute_pipeline,
+                trail_decay_compute_pipeline,
+                trail_diffuse_compute_pipeline,
+            }
+
+        };
+
+        let renderer = Renderer::with_layout(device, &pipelines.render_bind_group_layout, &RendererDescriptor {
+            format: desc.format,
+            transparent: false,
+        }, false);
+
+        let mut rng = match desc.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+
+        let buffers = {
+            let mut particle_buffers = Vec::<wgpu::Buffer>::new();
+            let mut trail_textures = Vec::<wgpu::Texture>::new();
+    
+            let initial_particle_data = random_particles(&mut rng, max_particles);
+
+            for i in 0..2 {
+                particle_buffers.push(
+                    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some(&format!("Particle Buffer {}", i)),
+                        contents: bytemuck::cast_slice(&initial_particle_data),
+                        usage: wgpu::BufferUsage::STORAGE
+                            | wgpu::BufferUsage::COPY_DST
+                            | wgpu::BufferUsage::COPY_SRC,
+                    }),
+                );
+    
+                trail_textures.push(device.create_texture(
+                    &wgpu::TextureDescriptor {
+                        label: Some(&format!("Trail Texture {}", i)),
+                        size: texture_size,
+                        mip_level_count: 1, 
+                        sample_count: 1,
+                        dimension: wgpu::TextureDimension::D2,
+                        format: wgpu::TextureFormat::R32Float,
+                        usage: wgpu::TextureUsage::STORAGE | wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_SRC | wgpu::TextureUsage::COPY_DST,
+                    }
+                ));
+            }
+
+            let particle_uniform = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Simulation Parameter Buffer"),
+                contents: &params.particle.to_bytes(),
+                usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            });
+
+            let decay_uniform = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Simulation Parameter Buffer"),
+                contents: &desc.params.decay.to_bytes(),
+                usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            });
+
+            let diffuse_uniform = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Simulation Parameter Buffer"),
+                contents: &desc.params.diffuse.to_bytes(),
+                usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            });
+
+            let render_uniform = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Simulation Parameter Buffer"),
+                contents: &desc.params.render.to_bytes(),
+                usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            });
+
+            SimBuffers {
+                particle_buffers,
+                trail_textures,
+                particle_uniform,
+                decay_uniform,
+                diffuse_uniform,
+                render_uniform
+            }
+        };
+
+        let bind_groups = {
+
+            let mut particle_bind_groups = Vec::<wgpu::BindGroup>::new();
+            let mut trail_decay_bind_groups = Vec::<wgpu::BindGroup>::new();
+            let mut trail_diffuse_bind_groups = Vec::<wgpu::BindGroup>::new();
+            let mut render_bind_groups = Vec::<wgpu::BindGroup>::new();
+
+            let desc = Default::default();
+
+            for i in 0..2 {
+                particle_bind_groups.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &pipelines.particle_bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: buffers.particle_uniform.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: buffers.particle_buffers[i].as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: buffers.particle_buffers[(i + 1) % 2].as_entire_binding(), // bind to opposite buffer
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 3,
+                            resource: wgpu::BindingResource::TextureView(&buffers.trail_textures[i].create_view(&desc)),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 4,
+                            resource: wgpu::BindingResource::TextureView(&buffers.trail_textures[(i + 1) % 2].create_view(&desc)), // bind to opposite buffer
+                        },
+                    ],
+                    label: None,
+                }));
+    
+                trail_decay_bind_groups.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &pipelines.decay_bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: buffers.decay_uniform.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::TextureView(&buffers.trail_textures[(i + 1) % 2].create_view(&desc)),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: wgpu::BindingResource::TextureView(&buffers.trail_textures[i].create_view(&desc)), // bind to opposite buffer
+                        },
+                    ],
+                    label: None,
+                }));
+    
+                trail_diffuse_bind_groups.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &pipelines.diffuse_bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: buffers.diffuse_uniform.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::TextureView(&buffers.trail_textures[i].create_view(&desc)),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: wgpu::BindingResource::TextureView(&buffers.trail_textures[(i + 1) % 2].create_view(&desc)), // bind to opposite buffer
+                        },
+                    ],
+                    label: None,
+                }));
+    
+                render_bind_groups.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    layout: &pipelines.render_bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: buffers.render_uniform.as_entire_binding(),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::TextureView(&buffers.trail_textures[(i + 1) % 2].create_view(&desc)),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: wgpu::BindingResource::Sampler(&sampler),
+                        },
+                    ],
+                    label: None,
+                }));
+            }
+
+            SimBindGroups {
+                particle_bind_groups,
+                trail_decay_bind_groups,
+                trail_diffuse_bind_groups,
+                render_bind_groups,
+            }
+
+        };
+
+        let trail_views = buffers.trail_textures.iter()
+            .map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()))
+            .collect();
+
+        // calculates number of work groups from PARTICLES_PER_GROUP constant
+        let particle_work_group_count = work_groups(params.particle.num_particles);
+
+        let screen_work_group_count: (u32, u32) = 
+            ((SCREEN_SIZE.0 as f32 / 16.0).ceil() as u32, (SCREEN_SIZE.1 as f32 / 16.0).ceil() as u32);
+
+        log::info!("Particle work group count: {}", particle_work_group_count);
+        log::info!("Screen work group count: {:?}", screen_work_group_count);
+
+        Simulation {
+            buffers,
+            bind_groups,
+            pipelines,
+            renderer,
+            trail_views,
+            particle_work_group_count,
+            screen_work_group_count,
+            max_particles,
+            frame: 0,
+            rng,
+            post_enabled: true,
+        }
+    }
+
+    /// Frames simulated so far
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    pub fn max_particles(&self) -> u32 {
+        self.max_particles
+    }
+
+    /// Uploads the uniforms of the sections flagged in `sections`, indexed like
+    /// `Params::sections`, and returns how many were written. `num_particles`
+    /// is limited to one less than `max_particles`
+    pub fn write_params(&mut self, queue: &wgpu::Queue, params: &Params, sections: [bool; 4]) -> usize {
+        let params = limit_particles(params, self.max_particles);
+        self.particle_work_group_count = work_groups(params.particle.num_particles);
+        let uniforms = [
+            &self.buffers.particle_uniform,
+            &self.buffers.decay_uniform,
+            &self.buffers.diffuse_uniform,
+            &self.buffers.render_uniform,
+        ];
+        let mut uploads = 0;
+        for ((section, buffer), dirty) in params.sections().iter().zip(uniforms.iter()).zip(sections.iter()) {
+            if *dirty {
+                queue.write_buffer(buffer, 0, &section.to_bytes());
+                uploads += 1;
+            }
+        }
+        uploads
+    }
+
+    /// Uploads every uniform
+    pub fn set_params(&mut self, queue: &wgpu::Queue, params: &Params) {
+        self.write_params(queue, params, [true; 4]);
+    }
+
+    /// Records the compute passes of one simulation step
+    pub fn step(&mut self, command_encoder: &mut wgpu::CommandEncoder) {
+        command_encoder.push_debug_group("compute particle movement");
+        {
+            let mut cpass =
+                command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+            cpass.set_pipeline(&self.pipelines.particle_compute_pipeline);
+            cpass.set_bind_group(0, &self.bind_groups.particle_bind_groups[self.frame % 2], &[]);
+            cpass.dispatch(self.particle_work_group_count, 1, 1);
+        }
+        command_encoder.pop_debug_group();
+
+        if self.post_enabled {
+            command_encoder.push_debug_group("compute trail decay");
+            {
+                let mut cpass =
+                    command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+                cpass.set_pipeline(&self.pipelines.trail_decay_compute_pipeline);
+                cpass.set_bind_group(0, &self.bind_groups.trail_decay_bind_groups[self.frame % 2], &[]);
+                cpass.dispatch(self.screen_work_group_count.0, self.screen_work_group_count.1, 1);
+            }
+            command_encoder.pop_debug_group();
+            command_encoder.push_debug_group("compute trail diffuse");
+            {
+                let mut cpass =
+                    command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+                cpass.set_pipeline(&self.pipelines.trail_diffuse_compute_pipeline);
+                cpass.set_bind_group(0, &self.bind_groups.trail_diffuse_bind_groups[self.frame % 2], &[]);
+                cpass.dispatch(self.screen_work_group_count.0, self.screen_work_group_count.1, 1);
+            }
+            command_encoder.pop_debug_group();
+        }
+
+        self.frame += 1;
+    }
+
+    /// R32Float trail intensities written by the last step, `SCREEN_SIZE` texels
+    pub fn trail_view(&self) -> &wgpu::TextureView {
+        &self.trail_views[self.frame % 2]
+    }
+
+    pub fn trail_texture(&self) -> &wgpu::Texture {
+        &self.buffers.trail_textures[self.frame % 2]
+    }
+
+    /// Records a render pass clearing `view` and drawing the trails of the last
+    /// step over it, `view` must have the descriptor's format
+    pub fn draw(&self, command_encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        self.renderer.draw(self, command_encoder, view, None, Some(wgpu::Color::BLACK));
+    }
+
+    pub(crate) fn render_bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.pipelines.render_bind_group_layout
+    }
+
+    /// Samples the trails of the last step, bind group i samples the texture
+    /// step i wrote and step has moved on since
+    pub(crate) fn render_bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_groups.render_bind_groups[(self.frame + 1) % 2]
+    }
+
+    /// Scatters the particles again and clears the trails
+    pub fn respawn(&mut self, queue: &wgpu::Queue) {
+        let data = random_particles(&mut self.rng, self.max_particles);
+        for buffer in &self.buffers.particle_buffers {
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(&data));
+        }
+        let blank = vec![0u8; (SCREEN_SIZE.0 * SCREEN_SIZE.1 * 4) as usize];
+        for texture in &self.buffers.trail_textures {
+            queue.write_texture(
+                wgpu::TextureCopyView {
+                    texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                &blank,
+                wgpu::TextureDataLayout {
+                    offset: 0,
+                    bytes_per_row: SCREEN_SIZE.0 * 4,
+                    rows_per_image: SCREEN_SIZE.1,
+                },
+                wgpu::Extent3d {
+                    width: SCREEN_SIZE.0,
+                    height: SCREEN_SIZE.1,
+                    depth: 1,
+                },
+            );
+        }
+    }
+}
+
+/// `params` with `num_particles` below `max_particles`, the shader indexes the
+/// particle buffers with it
+fn limit_particles(params: &Params, max_particles: u32) -> Cow<Params> {
+    let limit = max_particles.saturating_sub(1);
+    if params.particle.num_particles <= limit {
+        return Cow::Borrowed(params);
+    }
+    let mut limited = params.clone();
+    limited.particle.num_particles = limit;
+    Cow::Owned(limited)
+}
+
+fn work_groups(particles: u32) -> u32 {
+    (particles as f32 / PARTICLES_PER_GROUP as f32).ceil() as u32
+}
+
+/// Positions in [0, 1) and directions in [-1, 1), 4 floats per particle
+fn random_particles(rng: &mut impl Rng, count: u32) -> Vec<f32> {
+    let mut data = vec![0.0f32; 4 * count as usize];
+    for particle in data.chunks_mut(4) {
+        particle[0] = rng.gen::<f32>();
+        particle[1] = rng.gen::<f32>();
+        particle[2] = rng.gen::<f32>() * 2.0 - 1.0;
+        particle[3] = rng.gen::<f32>() * 2.0 - 1.0;
+    }
+    data
+}
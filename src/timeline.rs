@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+use crate::morph::lerp_value;
+use crate::params::{ParamValue, Params};
+
+/// Shape of the curve from a keyframe to the next one
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+    /// Hold the value until the next keyframe
+    Step,
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Default for Easing {
+    fn default() -> Self {
+        Easing::Linear
+    }
+}
+
+impl Easing {
+    pub const ALL: [Easing; 5] = [Easing::Step, Easing::Linear, Easing::EaseIn, Easing::EaseOut, Easing::EaseInOut];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Easing::Step => "Step",
+            Easing::Linear => "Linear",
+            Easing::EaseIn => "Ease in",
+            Easing::EaseOut => "Ease out",
+            Easing::EaseInOut => "Ease in-out",
+        }
+    }
+
+    /// Maps progress `t` in [0, 1] onto the curve
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.max(0.0).min(1.0);
+        match self {
+            Easing::Step => 0.0,
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Keyframe {
+    /// Seconds from the start of the timeline
+    pub time: f32,
+    pub value: ParamValue,
+    /// Curve towards the next keyframe
+    #[serde(default)]
+    pub easing: Easing,
+}
+
+/// Keyframes of a single field, kept sorted by time
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Track {
+    /// `section.field` path, e.g. `particle.sensor_angle`
+    pub path: String,
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl Track {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_owned(),
+            keyframes: Vec::new(),
+        }
+    }
+
+    /// Value at `time`, held before the first and after the last keyframe
+    pub fn evaluate(&self, time: f32) -> Option<ParamValue> {
+        let next = self.keyframes.iter().position(|k| k.time > time);
+        match next {
+            None => self.keyframes.last().map(|k| k.value),
+            Some(0) => Some(self.keyframes[0].value),
+            Some(next) => {
+                let (a, b) = (&self.keyframes[next - 1], &self.keyframes[next]);
+                let t = (time - a.time) / (b.time - a.time);
+                Some(lerp_value(a.value, b.value, a.easing.apply(t)))
+            }
+        }
+    }
+
+    /// Adds a keyframe, replacing one at the same time
+    pub fn insert(&mut self, keyframe: Keyframe) {
+        match self.keyframes.iter().position(|k| (k.time - keyframe.time).abs() < 1e-3) {
+            Some(index) => self.keyframes[index] = keyframe,
+            None => {
+                self.keyframes.push(keyframe);
+                self.sort();
+            }
+        }
+    }
+
+    /// Restores time order after keyframe times were edited
+    pub fn sort(&mut self) {
+        self.keyframes.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(std::cmp::Ordering::Equal));
+    }
+}
+
+/// Animated fields, saved with the user presets
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Timeline {
+    pub tracks: Vec<Track>,
+    /// Seconds until the playhead stops or loops
+    pub duration: f32,
+    #[serde(default)]
+    pub looping: bool,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self {
+            tracks: Vec::new(),
+            duration: 30.0,
+            looping: false,
+        }
+    }
+}
+
+impl Timeline {
+    /// Overrides the animated fields of `params` with their values at `time`,
+    /// returns the sections that were touched, indexed like `Params::sections`
+    pub fn apply(&self, time: f32, params: &mut Params) -> [bool; 4] {
+        let mut touched = [false; 4];
+        for track in &self.tracks {
+            let value = match track.evaluate(time) {
+                Some(value) => value,
+                None => continue,
+            };
+            let section = params.sections().iter().position(|s| track.path.starts_with(&format!("{}.", s.key())));
+            if params.set_field(&track.path, value) {
+                if let Some(section) = section {
+                    touched[section] = true;
+                }
+            }
+        }
+        touched
+    }
+}
+
+/// Playback state of the timeline, not saved
+#[derive(Debug, Default)]
+pub struct Playhead {
+    pub time: f32,
+    pub playing: bool,
+    /// Set when the evaluated values may have changed and need uploading
+    pub changed: bool,
+}
+
+impl Playhead {
+    /// Advances by `dt` seconds while playing
+    pub fn tick(&mut self, dt: f32, timeline: &Timeline) {
+        if !self.playing {
+            return;
+        }
+        self.time += dt;
+        if self.time >= timeline.duration {
+            if timeline.looping && timeline.duration > 0.0 {
+                self.time %= timeline.duration;
+            } else {
+                self.time = timeline.duration;
+                self.playing = false;
+            }
+        }
+        self.changed = true;
+    }
+
+    pub fn seek(&mut self, time: f32) {
+        self.time = time.max(0.0);
+        self.changed = true;
+    }
+}
@@ -1,14 +1,100 @@
-pub trait Uniform: Sized {
+use crate::params::{ParamValue, Reflect};
+
+/// Uniform upload generated from a section's reflected fields, in declaration order
+pub trait Uniform: Reflect {
     fn memsize(&self) -> Option<wgpu::BufferSize> {
-        log::info!("size: {}", std::mem::size_of::<Self>() as u64);
-        std::num::NonZeroU64::new(std::mem::size_of::<Self>() as u64)
+        let size = self.to_bytes().len() as u64;
+        log::info!("size: {}", size);
+        std::num::NonZeroU64::new(size)
     }
 
-    fn to_bytes(&self) -> &[u8] {
-        let p: *const Self = self;     
-        let p: *const u8 = p as *const u8;  
-        unsafe { 
-            std::slice::from_raw_parts(p, std::mem::size_of::<Self>())
+    fn encode(&self) -> UniformEncoder {
+        let mut encoder = UniformEncoder::new();
+        for index in 0..self.fields().len() {
+            match self.get(index) {
+                ParamValue::Float(v) => encoder.f32(v),
+                ParamValue::Count(v) => encoder.u32(v),
+                // the shaders declare colors as three scalar members
+                ParamValue::Color(c) => c.iter().for_each(|v| encoder.f32(*v)),
+            }
         }
+        encoder
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.encode().finish()
+    }
+}
+
+impl<T: Reflect + ?Sized> Uniform for T {}
+
+/// Writes struct members following the WGSL uniform (std140) layout rules,
+/// padding every member to its alignment and the struct to 16 bytes
+#[derive(Debug, Default)]
+pub struct UniformEncoder {
+    bytes: Vec<u8>,
+    offsets: Vec<u32>,
+}
+
+#[allow(dead_code)]
+impl UniformEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Byte offset of every member written so far
+    pub fn offsets(&self) -> &[u32] {
+        &self.offsets
+    }
+
+    pub fn f32(&mut self, v: f32) {
+        self.member(4, &[v.to_le_bytes()]);
+    }
+
+    pub fn u32(&mut self, v: u32) {
+        self.member(4, &[v.to_le_bytes()]);
+    }
+
+    pub fn vec2(&mut self, v: [f32; 2]) {
+        self.member(8, &[v[0].to_le_bytes(), v[1].to_le_bytes()]);
+    }
+
+    /// vec3 is aligned like a vec4, a following scalar may use the last 4 bytes
+    pub fn vec3(&mut self, v: [f32; 3]) {
+        self.member(16, &[v[0].to_le_bytes(), v[1].to_le_bytes(), v[2].to_le_bytes()]);
+    }
+
+    pub fn vec4(&mut self, v: [f32; 4]) {
+        self.member(16, &[v[0].to_le_bytes(), v[1].to_le_bytes(), v[2].to_le_bytes(), v[3].to_le_bytes()]);
+    }
+
+    /// Uniform arrays have a stride of 16 bytes, even for scalar elements
+    pub fn f32_array(&mut self, values: &[f32]) {
+        self.align(16);
+        self.offsets.push(self.bytes.len() as u32);
+        for v in values {
+            self.align(16);
+            self.bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        self.align(16);
+    }
+
+    /// Pads the struct to its final size
+    pub fn finish(mut self) -> Vec<u8> {
+        self.align(16);
+        self.bytes
+    }
+
+    fn member(&mut self, align: usize, components: &[[u8; 4]]) {
+        self.align(align);
+        self.offsets.push(self.bytes.len() as u32);
+        for c in components {
+            self.bytes.extend_from_slice(c);
+        }
+    }
+
+    fn align(&mut self, align: usize) {
+        let padded = (self.bytes.len() + align - 1) / align * align;
+        self.bytes.resize(padded, 0);
     }
-}
\ No newline at end of file
+}
@@ -16,4 +16,48 @@ pub fn create_shader(device: &wgpu::Device, path: &str) -> wgpu::ShaderModule {
     })
 }
 
+/// Copies a 4 bytes per pixel texture back to the CPU, rows tightly packed
+pub fn read_texture(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture, width: u32, height: u32) -> Result<Vec<u8>, String> {
+    // rows of a texture copy are padded to 256 bytes
+    let row_bytes = width * 4;
+    let padded_row_bytes = (row_bytes + wgpu::COPY_BYTES_PER_ROW_ALIGNMENT - 1)
+        / wgpu::COPY_BYTES_PER_ROW_ALIGNMENT
+        * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let readback = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Texture Readback"),
+        size: (padded_row_bytes * height) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsage::COPY_DST | wgpu::BufferUsage::MAP_READ,
+        mapped_at_creation: false,
+    });
 
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    encoder.copy_texture_to_buffer(
+        wgpu::TextureCopyView {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+        },
+        wgpu::BufferCopyView {
+            buffer: &readback,
+            layout: wgpu::TextureDataLayout {
+                offset: 0,
+                bytes_per_row: padded_row_bytes,
+                rows_per_image: height,
+            },
+        },
+        wgpu::Extent3d { width, height, depth: 1 },
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let slice = readback.slice(..);
+    let mapping = slice.map_async(wgpu::MapMode::Read);
+    device.poll(wgpu::Maintain::Wait);
+    pollster::block_on(mapping).map_err(|e| format!("Error reading texture: {:?}", e))?;
+    let pixels = slice
+        .get_mapped_range()
+        .chunks(padded_row_bytes as usize)
+        .flat_map(|row| row[..row_bytes as usize].iter().copied())
+        .collect();
+    readback.unmap();
+    Ok(pixels)
+}
@@ -0,0 +1,189 @@
+// Sends raw HTTP requests to the API server and answers them from a stand-in
+// render loop, checking the routes and the calls they produce, and streams
+// events to a WebSocket client.
+
+use moldsim::{api, params, remote};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use api::{ApiServer, Call, Reply};
+use remote::Command;
+
+/// Sends one request and returns the status code and body
+fn send(addr: SocketAddr, method: &str, path: &str, body: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+        method, path, body.len(), body,
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let status = response.split(' ').nth(1).and_then(|code| code.parse().ok()).unwrap();
+    let body = response.split("\r\n\r\n").nth(1).unwrap_or_default().to_owned();
+    (status, body)
+}
+
+/// Serves requests on a fresh server while `answer` plays the render loop,
+/// returns the calls it received
+fn serve(requests: &[(&str, &str, &str)], answer: fn(&Call) -> Reply) -> (Vec<(u16, String)>, Vec<String>) {
+    let server = ApiServer::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr();
+    let requests: Vec<_> = requests.iter().map(|(m, p, b)| (m.to_string(), p.to_string(), b.to_string())).collect();
+    let (sender, responses) = mpsc::channel();
+    std::thread::spawn(move || {
+        let responses = requests.iter().map(|(m, p, b)| send(addr, m, p, b)).collect::<Vec<_>>();
+        sender.send(responses).unwrap();
+    });
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let mut calls = Vec::new();
+    while Instant::now() < deadline {
+        for request in server.poll() {
+            calls.push(format!("{:?}", request.call));
+            let reply = answer(&request.call);
+            request.reply(reply);
+        }
+        if let Ok(responses) = responses.try_recv() {
+            return (responses, calls);
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    panic!("the client did not finish");
+}
+
+#[test]
+fn routes_map_to_calls() {
+    let (responses, calls) = serve(
+        &[
+            ("POST", "/presets/select", r#"{"name": "Disperse"}"#),
+            ("POST", "/presets/select", r#"{"index": 2}"#),
+            ("POST", "/pause", ""),
+            ("POST", "/step", ""),
+            ("POST", "/resume", ""),
+            ("POST", "/reset", ""),
+        ],
+        |_| Reply::Json(serde_json::json!({ "ok": true })),
+    );
+    assert!(responses.iter().all(|(status, body)| *status == 200 && body == r#"{"ok":true}"#));
+    let expected: Vec<String> = vec![
+        Command::Preset("Disperse".to_owned()),
+        Command::PresetIndex(2),
+        Command::Pause(true),
+        Command::Step,
+        Command::Pause(false),
+        Command::Reset,
+    ]
+    .into_iter()
+    .map(|command| format!("{:?}", Call::Command(command)))
+    .collect();
+    assert_eq!(calls, expected);
+}
+
+#[test]
+fn params_roundtrip_as_json() {
+    let (responses, calls) = serve(
+        &[("GET", "/params", ""), ("PUT", "/params", "")],
+        |call| match call {
+            Call::GetParams => Reply::Json(serde_json::to_value(params::ParamManager::builtin().current()).unwrap()),
+            _ => Reply::Json(serde_json::json!({ "ok": true })),
+        },
+    );
+    // the empty PUT body never reaches the render loop
+    assert_eq!(calls, vec!["GetParams".to_owned()]);
+    assert_eq!(responses[1].0, 400);
+
+    let (status, body) = &responses[0];
+    assert_eq!(*status, 200);
+    let (responses, calls) = serve(&[("PUT", "/params", body)], |_| Reply::Json(serde_json::json!({ "ok": true })));
+    assert_eq!(responses[0].0, 200);
+    assert_eq!(calls.len(), 1);
+    assert!(calls[0].starts_with("PutParams"));
+}
+
+#[test]
+fn bad_requests_are_rejected() {
+    let (responses, calls) = serve(
+        &[
+            ("GET", "/nonexistent", ""),
+            ("POST", "/presets/select", r#"{"preset": 1}"#),
+            ("GET", "/pause", ""),
+        ],
+        |_| Reply::Error(500, "unexpected call".to_owned()),
+    );
+    let statuses: Vec<u16> = responses.iter().map(|(status, _)| *status).collect();
+    assert_eq!(statuses, vec![404, 400, 404]);
+    assert!(calls.is_empty());
+}
+
+/// Polls the server until `done` returns true, answering with `answer`
+fn poll_until(server: &ApiServer, mut answer: impl FnMut(api::Request), mut done: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while Instant::now() < deadline {
+        server.poll().for_each(&mut answer);
+        if done() {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    panic!("timed out");
+}
+
+#[test]
+fn a_pending_request_does_not_hold_up_others() {
+    let server = ApiServer::bind("127.0.0.1:0").unwrap();
+    let addr = server.local_addr();
+    std::thread::spawn(move || send(addr, "GET", "/params", ""));
+
+    // the render loop holds on to the first request
+    let mut held = Vec::new();
+    let waiting = std::cell::Cell::new(false);
+    poll_until(&server, |request| {
+        waiting.set(true);
+        held.push(request);
+    }, || waiting.get());
+
+    let (sender, responses) = mpsc::channel();
+    std::thread::spawn(move || sender.send(send(addr, "GET", "/presets", "")).unwrap());
+    let mut response = None;
+    poll_until(
+        &server,
+        |request| request.reply(Reply::Json(serde_json::json!({ "ok": true }))),
+        || {
+            response = responses.try_recv().ok();
+            response.is_some()
+        },
+    );
+    assert_eq!(response, Some((200, r#"{"ok":true}"#.to_owned())));
+    assert_eq!(held.len(), 1);
+}
+
+#[test]
+fn websocket_clients_get_events_and_answers() {
+    use tungstenite::Message;
+
+    let server = ApiServer::bind("127.0.0.1:0").unwrap();
+    let stream = TcpStream::connect(server.ws_addr()).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+    let (mut socket, _) = tungstenite::client(format!("ws://{}/", server.ws_addr()).as_str(), stream).unwrap();
+    poll_until(&server, |_| {}, || server.has_listeners());
+
+    server.broadcast(&serde_json::json!({ "type": "stats" }));
+    assert_eq!(socket.read_message().unwrap(), Message::Text(r#"{"type":"stats"}"#.to_owned()));
+
+    socket.write_message(Message::Ping(b"ping".to_vec())).unwrap();
+    assert_eq!(socket.read_message().unwrap(), Message::Pong(b"ping".to_vec()));
+
+    // the server acknowledges the close and forgets the client
+    socket.close(None).unwrap();
+    loop {
+        match socket.read_message() {
+            Ok(_) => {}
+            Err(tungstenite::Error::ConnectionClosed) => break,
+            Err(e) => panic!("{}", e),
+        }
+    }
+    poll_until(&server, |_| {}, || !server.has_listeners());
+}
@@ -0,0 +1,75 @@
+// Feeds control changes through the MIDI bindings without hardware and checks
+// the recording format used for replays.
+
+use moldsim::{midi, params, remote};
+use midi::{ControlChange, MidiBinding, Playback, Recording};
+use params::ParamValue;
+use remote::Command;
+
+fn cc(channel: u8, controller: u8, value: u8) -> ControlChange {
+    ControlChange { channel, controller, value }
+}
+
+#[test]
+fn raw_messages_parse_as_control_changes() {
+    assert_eq!(ControlChange::parse(&[0xB0, 16, 64]), Some(cc(1, 16, 64)));
+    assert_eq!(ControlChange::parse(&[0xBF, 7, 127]), Some(cc(16, 7, 127)));
+    // note on, and a truncated message
+    assert_eq!(ControlChange::parse(&[0x90, 60, 100]), None);
+    assert_eq!(ControlChange::parse(&[0xB0, 16]), None);
+}
+
+#[test]
+fn learned_bindings_scale_to_the_field_range() {
+    let mut bindings = Vec::new();
+    assert!(midi::bindable("particle.speed"));
+    assert!(!midi::bindable("render.color_1"));
+    assert!(!midi::bindable("render.nonexistent"));
+
+    midi::bind(&mut bindings, &cc(1, 16, 0), "particle.speed");
+    midi::bind(&mut bindings, &cc(1, 17, 0), "decay.decay_rate");
+    midi::bind(&mut bindings, &cc(2, 16, 0), "particle.num_particles");
+    // rebinding a controller or a field replaces the old binding
+    midi::bind(&mut bindings, &cc(1, 17, 0), "render.cutoff");
+    midi::bind(&mut bindings, &cc(1, 18, 0), "particle.speed");
+    assert_eq!(bindings, vec![
+        MidiBinding { channel: 2, controller: 16, path: "particle.num_particles".to_owned() },
+        MidiBinding { channel: 1, controller: 17, path: "render.cutoff".to_owned() },
+        MidiBinding { channel: 1, controller: 18, path: "particle.speed".to_owned() },
+    ]);
+
+    assert_eq!(midi::commands(&cc(1, 18, 127), &bindings, 1000), vec![
+        Command::Set { path: "particle.speed".to_owned(), value: ParamValue::Float(15.0) },
+    ]);
+    assert_eq!(midi::commands(&cc(2, 16, 127), &bindings, 1001), vec![
+        Command::Set { path: "particle.num_particles".to_owned(), value: ParamValue::Count(1000) },
+    ]);
+    assert_eq!(midi::commands(&cc(1, 17, 0), &bindings, 1000), vec![
+        Command::Set { path: "render.cutoff".to_owned(), value: ParamValue::Float(0.0) },
+    ]);
+    assert!(midi::commands(&cc(1, 16, 64), &bindings, 1000).is_empty());
+}
+
+#[test]
+fn recordings_roundtrip_and_replay_in_order() {
+    let recording = Recording::parse("
+        # seconds channel controller value
+        0.5 1 16 127
+        0.0 1 16 0   # sorted by time
+        1.0 3 20 64
+    ").unwrap();
+    assert_eq!(recording.events, vec![(0.0, cc(1, 16, 0)), (0.5, cc(1, 16, 127)), (1.0, cc(3, 20, 64))]);
+    assert_eq!(Recording::parse(&recording.to_string()).unwrap(), recording);
+
+    assert!(Recording::parse("0.0 1 16").is_err());
+    assert!(Recording::parse("0.0 17 16 0").is_err());
+    assert!(Recording::parse("0.0 1 16 128").is_err());
+
+    let mut playback = Playback::new(recording);
+    assert_eq!(playback.tick(0.25), vec![cc(1, 16, 0)]);
+    assert_eq!(playback.tick(0.25), vec![cc(1, 16, 127)]);
+    assert!(playback.tick(0.25).is_empty());
+    assert!(!playback.finished());
+    assert_eq!(playback.tick(1.0), vec![cc(3, 20, 64)]);
+    assert!(playback.finished());
+}
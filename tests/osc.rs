@@ -0,0 +1,92 @@
+// Sends OSC packets from a local UDP client and checks the commands the
+// listener produces, and how they apply to the presets.
+
+use moldsim::{osc, params, remote};
+use std::net::UdpSocket;
+use std::time::{Duration, Instant};
+use params::{ParamManager, ParamValue};
+use remote::Command;
+use rosc::{OscMessage, OscPacket, OscType};
+
+fn message(addr: &str, args: Vec<OscType>) -> OscPacket {
+    OscPacket::Message(OscMessage { addr: addr.to_owned(), args })
+}
+
+/// Sends `packets` to a fresh listener and collects `count` commands
+fn roundtrip(packets: &[OscPacket], count: usize) -> Vec<Command> {
+    let server = osc::OscServer::bind("127.0.0.1:0").unwrap();
+    let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+    for packet in packets {
+        let buf = rosc::encoder::encode(packet).unwrap();
+        client.send_to(&buf, server.local_addr()).unwrap();
+    }
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut commands = Vec::new();
+    while commands.len() < count && Instant::now() < deadline {
+        commands.extend(server.poll());
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    commands
+}
+
+#[test]
+fn messages_map_to_commands() {
+    let commands = roundtrip(
+        &[
+            message("/moldsim/particle/speed", vec![OscType::Float(4.5)]),
+            message("/moldsim/particle/num_particles", vec![OscType::Int(1000)]),
+            message("/moldsim/render/color_1", vec![OscType::Float(1.0), OscType::Float(0.5), OscType::Double(0.0)]),
+            message("/moldsim/preset", vec![OscType::String("Disperse".to_owned())]),
+            message("/moldsim/preset", vec![OscType::Int(2)]),
+            message("/moldsim/reset", vec![]),
+        ],
+        6,
+    );
+    assert_eq!(commands, vec![
+        Command::Set { path: "particle.speed".to_owned(), value: ParamValue::Float(4.5) },
+        Command::Set { path: "particle.num_particles".to_owned(), value: ParamValue::Count(1000) },
+        Command::Set { path: "render.color_1".to_owned(), value: ParamValue::Color([1.0, 0.5, 0.0]) },
+        Command::Preset("Disperse".to_owned()),
+        Command::PresetIndex(2),
+        Command::Reset,
+    ]);
+}
+
+#[test]
+fn bad_messages_are_skipped() {
+    let commands = roundtrip(
+        &[
+            message("/moldsim/particle/nonexistent", vec![OscType::Float(1.0)]),
+            message("/other/particle/speed", vec![OscType::Float(1.0)]),
+            message("/moldsim/diffuse/diffuse_amount", vec![OscType::String("a lot".to_owned())]),
+            message("/moldsim/render/color_2", vec![OscType::Float(1.0)]),
+            message("/moldsim/diffuse/diffuse_amount", vec![OscType::Float(0.25)]),
+        ],
+        1,
+    );
+    assert_eq!(commands, vec![
+        Command::Set { path: "diffuse.diffuse_amount".to_owned(), value: ParamValue::Float(0.25) },
+    ]);
+}
+
+#[test]
+fn set_path_clamps_to_the_field_range() {
+    let mut params = ParamManager::builtin();
+    params.take_dirty();
+
+    assert!(params.set_path("particle.speed", ParamValue::Float(100.0)));
+    assert_eq!(params.current().particle.speed, 15.0);
+    assert_eq!(params.take_dirty(), [true, false, false, false]);
+
+    let max = params.global.max_particles;
+    assert!(params.set_path("particle.num_particles", ParamValue::Count(max + 10)));
+    assert_eq!(params.current().particle.num_particles, max - 1);
+
+    assert!(!params.set_path("particle.speed", ParamValue::Count(1)));
+    assert!(!params.set_path("render.nonexistent", ParamValue::Float(1.0)));
+
+    assert!(params.select_by_name("Disperse"));
+    assert_eq!(params.current().name, "Disperse");
+    assert!(!params.select_by_name("No such preset"));
+}
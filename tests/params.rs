@@ -0,0 +1,139 @@
+// Checks how preset names are cleaned up and made unique, and what playlists change.
+
+use moldsim::params::ParamManager;
+
+fn manager(names: &[&str]) -> ParamManager {
+    let mut manager = ParamManager::builtin();
+    let template = manager.current().clone();
+    manager.params = names.iter()
+        .map(|name| moldsim::params::Params { name: name.to_string(), builtin: false, ..template.clone() })
+        .collect();
+    manager.current = 0;
+    manager
+}
+
+#[test]
+fn free_names_are_trimmed() {
+    let manager = manager(&["Default"]);
+    assert_eq!(manager.unique_name("  Spores ", None), "Spores");
+}
+
+#[test]
+fn empty_names_become_untitled() {
+    let manager = manager(&["Default"]);
+    assert_eq!(manager.unique_name("", None), "Untitled");
+    assert_eq!(manager.unique_name("   ", None), "Untitled");
+}
+
+#[test]
+fn taken_names_get_the_next_free_suffix() {
+    let manager = manager(&["Default", "D", "D 2"]);
+    assert_eq!(manager.unique_name("D", None), "D 3");
+    assert_eq!(manager.unique_name(" Default ", None), "Default 2");
+}
+
+#[test]
+fn a_preset_keeps_its_own_name() {
+    let manager = manager(&["Default", "D"]);
+    assert_eq!(manager.unique_name("D", Some(1)), "D");
+    assert_eq!(manager.unique_name("D", Some(0)), "D 2");
+}
+
+#[test]
+fn rename_updates_playlist_entries() {
+    let mut manager = manager(&["Default", "D"]);
+    manager.playlist.entries.push(moldsim::playlist::PlaylistEntry::new("Default"));
+    manager.rename("D");
+    assert_eq!(manager.current().name, "D 2");
+    assert_eq!(manager.playlist.entries[0].name, "D 2");
+}
+
+#[test]
+fn editing_a_builtin_edits_a_copy() {
+    let mut manager = ParamManager::builtin();
+    let builtin = manager.current().clone();
+    let count = manager.params.len();
+    let speed = builtin.particle.speed;
+
+    // setting the current value is not an edit
+    assert!(manager.set_path("particle.speed", moldsim::params::ParamValue::Float(speed)));
+    assert_eq!(manager.params.len(), count);
+
+    let speed = if speed > 1.0 { speed - 1.0 } else { speed + 1.0 };
+    assert!(manager.set_path("particle.speed", moldsim::params::ParamValue::Float(speed)));
+    assert_eq!(manager.params.len(), count + 1);
+    assert_eq!(manager.params[manager.current - 1], builtin);
+    assert!(!manager.current().builtin);
+    assert_eq!(manager.current().name, format!("{} copy", builtin.name));
+    assert_eq!(manager.current().particle.speed, speed);
+
+    // later edits stay on the copy
+    assert!(manager.set_path("particle.speed", moldsim::params::ParamValue::Float(builtin.particle.speed)));
+    assert_eq!(manager.params.len(), count + 1);
+}
+
+#[test]
+fn the_first_edit_of_a_builtin_can_be_undone() {
+    let mut manager = ParamManager::builtin();
+    let builtin = manager.current().clone();
+    let speed = builtin.particle.speed;
+    let speed = if speed > 1.0 { speed - 1.0 } else { speed + 1.0 };
+
+    manager.observe_edits(false);
+    assert!(manager.set_path("particle.speed", moldsim::params::ParamValue::Float(speed)));
+    manager.observe_edits(false);
+    assert!(manager.can_undo());
+
+    manager.undo();
+    assert_eq!(manager.current().particle.speed, builtin.particle.speed);
+    assert_eq!(manager.current().name, format!("{} copy", builtin.name));
+    assert!(!manager.current().builtin);
+}
+
+#[test]
+fn a_temporary_playlist_leaves_the_saved_one_alone() {
+    use moldsim::playlist::{Playlist, PlaylistEntry, Scheduler};
+
+    let mut manager = manager(&["A", "B", "C"]);
+    manager.playlist.entries.push(PlaylistEntry::new("C"));
+    let saved = manager.playlist.clone();
+    let entry = |name| PlaylistEntry { duration: 1.0, transition: 0.0, ..PlaylistEntry::new(name) };
+    let mut scheduler = Scheduler::with_playlist(Playlist { entries: vec![entry("B"), entry("A")], looping: false });
+
+    scheduler.play(0, &mut manager);
+    assert_eq!(manager.current, 1);
+    scheduler.tick(1.0, &mut manager);
+    assert_eq!(manager.current, 0);
+    scheduler.tick(1.0, &mut manager);
+    assert!(!scheduler.is_playing());
+    assert_eq!(manager.playlist, saved);
+
+    scheduler.clear_temporary();
+    scheduler.play(0, &mut manager);
+    assert_eq!(manager.current, 2);
+}
+
+#[test]
+fn the_particle_override_outlasts_preset_switches() {
+    let mut manager = manager(&["A", "B"]);
+    manager.global.num_particles = Some(1000);
+    assert_eq!(manager.effective().particle.num_particles, 1000);
+    manager.select(1);
+    assert_eq!(manager.effective().particle.num_particles, 1000);
+    assert_ne!(manager.current().particle.num_particles, 1000);
+}
+
+#[test]
+fn clamped_presets_stay_in_range() {
+    use moldsim::params::{ParamValue, Params};
+
+    let mut params = Params::default();
+    assert!(params.set_field("particle.speed", ParamValue::Float(1e9)));
+    assert!(params.set_field("particle.num_particles", ParamValue::Count(u32::MAX)));
+    let clamped = params.clone().clamped(1000).unwrap();
+    assert_eq!(clamped.particle.speed, 15.0);
+    assert_eq!(clamped.particle.num_particles, 999);
+
+    assert!(params.set_field("particle.speed", ParamValue::Float(f32::INFINITY)));
+    assert!(params.clamped(1000).is_err());
+}
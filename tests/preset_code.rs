@@ -0,0 +1,80 @@
+// Round-trips presets through copy/paste codes and checks that damaged or
+// out of range codes are rejected or clamped.
+
+use moldsim::{params, preset_code};
+use params::{ParamKind, ParamManager, ParamValue, Params};
+
+const MAX_PARTICLES: u32 = 1 << 20;
+
+fn preset() -> Params {
+    ParamManager::builtin().current().clone()
+}
+
+fn raw(code: &str) -> Vec<u8> {
+    base64::decode_config(code, base64::URL_SAFE_NO_PAD).unwrap()
+}
+
+/// Encodes `bytes` with a fresh checksum
+fn seal(mut bytes: Vec<u8>) -> String {
+    let checksum = crc32fast::hash(&bytes);
+    bytes.extend_from_slice(&checksum.to_le_bytes());
+    base64::encode_config(&bytes, base64::URL_SAFE_NO_PAD)
+}
+
+#[test]
+fn roundtrip_keeps_values_within_quantization() {
+    let original = preset();
+    let decoded = preset_code::decode(&preset_code::encode(&original), MAX_PARTICLES).unwrap();
+    assert_eq!(decoded.name, original.name);
+    for (a, b) in original.sections().iter().zip(decoded.sections().iter()) {
+        for (index, field) in a.fields().iter().enumerate() {
+            match (field.kind, a.get(index), b.get(index)) {
+                (ParamKind::Float { min, max }, ParamValue::Float(x), ParamValue::Float(y)) => {
+                    assert!((x - y).abs() <= (max - min) / 65535.0, "{}: {} != {}", field.name, x, y);
+                }
+                (ParamKind::Count, x, y) => assert_eq!(x, y, "{}", field.name),
+                (ParamKind::Color, ParamValue::Color(x), ParamValue::Color(y)) => {
+                    for (x, y) in x.iter().zip(y.iter()) {
+                        assert!((x - y).abs() <= 0.5 / 255.0 + 1e-6, "{}: {} != {}", field.name, x, y);
+                    }
+                }
+                (_, x, y) => panic!("{}: {:?} decoded as {:?}", field.name, x, y),
+            }
+        }
+    }
+}
+
+#[test]
+fn surrounding_whitespace_is_ignored() {
+    let code = format!("  {}\n", preset_code::encode(&preset()));
+    assert!(preset_code::decode(&code, MAX_PARTICLES).is_ok());
+}
+
+#[test]
+fn checksum_mismatch_is_rejected() {
+    let mut bytes = raw(&preset_code::encode(&preset()));
+    bytes[2] ^= 0x01;
+    let code = base64::encode_config(&bytes, base64::URL_SAFE_NO_PAD);
+    let error = preset_code::decode(&code, MAX_PARTICLES).unwrap_err();
+    assert!(error.contains("Checksum"), "{}", error);
+}
+
+#[test]
+fn truncated_codes_are_rejected() {
+    let code = preset_code::encode(&preset());
+    for len in [0, 4, code.len() / 2, code.len() - 3].iter() {
+        assert!(preset_code::decode(&code[..*len], MAX_PARTICLES).is_err(), "accepted {} chars", len);
+    }
+    // still checksummed, but missing the last field
+    let bytes = raw(&code);
+    let error = preset_code::decode(&seal(bytes[..bytes.len() - 5].to_vec()), MAX_PARTICLES).unwrap_err();
+    assert!(error.contains("ends unexpectedly"), "{}", error);
+}
+
+#[test]
+fn particle_count_is_clamped_to_the_buffers() {
+    let mut params = preset();
+    params.particle.num_particles = u32::MAX;
+    let decoded = preset_code::decode(&preset_code::encode(&params), 1000).unwrap();
+    assert_eq!(decoded.particle.num_particles, 999);
+}
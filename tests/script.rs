@@ -0,0 +1,112 @@
+// Runs small scripts from temporary files and checks how their results are
+// copied into the params.
+
+use moldsim::{params, remote, script};
+use params::Params;
+use remote::Command;
+use script::Script;
+
+fn load(name: &str, source: &str) -> Script {
+    let path = std::env::temp_dir().join(format!("moldsim-{}-{}.rhai", name, std::process::id()));
+    std::fs::write(&path, source).unwrap();
+    let script = Script::load(&path);
+    std::fs::remove_file(&path).unwrap();
+    script
+}
+
+#[test]
+fn returned_params_are_clamped_and_marked() {
+    let mut script = load("clamp", "
+        fn on_frame(t, params) {
+            params.particle.speed = t * 1000.0;
+            params.render.color_1 = [2, 0.5, -1.0];
+            params
+        }
+    ");
+    assert_eq!(script.error(), None);
+
+    // builtin values are within the field ranges, unlike Params::default
+    let mut params = params::ParamManager::builtin().current().clone();
+    params.particle.speed = 1.0;
+    params.render.color_1 = [0.0; 3];
+    assert_eq!(script.run(0.5, &mut params, 100), [true, false, false, true]);
+    // t starts at zero
+    assert_eq!(params.particle.speed, 0.0);
+    assert_eq!(params.render.color_1, [1.0, 0.5, 0.0]);
+
+    // render changed in the previous call and is uploaded once more
+    assert_eq!(script.run(0.5, &mut params, 100), [true, false, false, true]);
+    assert_eq!(params.particle.speed, 15.0);
+    assert_eq!(script.time, 1.0);
+    assert_eq!(script.run(0.5, &mut params, 100), [true, false, false, false]);
+}
+
+#[test]
+fn sections_left_alone_are_uploaded_again() {
+    let mut script = load("unscripted", "
+        fn on_frame(t, params) {
+            if t < 1.0 {
+                params.particle.speed = 3.0;
+                return params;
+            }
+            if t < 2.0 {
+                return;
+            }
+            params.particle.speed = 4.0;
+            params.render.color_1 = \"bad\";
+            params
+        }
+    ");
+    let builtin = params::ParamManager::builtin().current().clone();
+    let mut params = builtin.clone();
+    params.particle.speed = 1.0;
+    assert_eq!(script.run(1.0, &mut params, 100), [true, false, false, false]);
+
+    // returning nothing goes back to the values of the preset
+    let mut params = builtin.clone();
+    assert_eq!(script.run(0.5, &mut params, 100), [true, false, false, false]);
+    assert_eq!(script.run(0.5, &mut params, 100), [false; 4]);
+
+    // a failed call changes nothing
+    let mut params = builtin.clone();
+    assert_eq!(script.run(1.0, &mut params, 100), [false; 4]);
+    assert!(script.error().is_some());
+    assert_eq!(params, builtin);
+}
+
+#[test]
+fn commands_are_queued() {
+    let mut script = load("commands", r#"
+        fn on_frame(t, params) {
+            preset("Disperse");
+            preset(2);
+            reset();
+        }
+    "#);
+    let mut params = params::ParamManager::builtin().current().clone();
+    let before = params.clone();
+    assert_eq!(script.run(0.1, &mut params, 100), [false; 4]);
+    assert_eq!(params, before);
+    assert_eq!(script.take_commands(), vec![
+        Command::Preset("Disperse".to_owned()),
+        Command::PresetIndex(2),
+        Command::Reset,
+    ]);
+    assert!(script.take_commands().is_empty());
+}
+
+#[test]
+fn errors_are_reported() {
+    let script = load("syntax", "fn on_frame(t, params) { params.particle.speed = ; }");
+    assert!(script.error().is_some());
+
+    let mut script = load("runtime", "fn on_frame(t, params) { params.particle.speed = \"fast\"; params }");
+    assert_eq!(script.error(), None);
+    let mut params = Params::default();
+    assert_eq!(script.run(0.1, &mut params, 100), [false; 4]);
+    assert!(script.error().unwrap().contains("particle.speed"));
+
+    let mut script = load("loop", "fn on_frame(t, params) { loop { } }");
+    script.run(0.1, &mut params, 100);
+    assert!(script.error().is_some());
+}
@@ -0,0 +1,114 @@
+// Checks the uniform encoding of every parameter section against the struct
+// layout naga reports for the WGSL shader that consumes it.
+
+use moldsim::{params, uniform};
+use params::{ParamValue, Reflect};
+use uniform::{Uniform, UniformEncoder};
+
+fn round_up(value: u32, align: u32) -> u32 {
+    (value + align - 1) / align * align
+}
+
+/// Offsets and scalar kinds of the uniform struct members, and the padded struct size
+fn wgsl_uniform_layout(shader: &str) -> (Vec<(u32, naga::ScalarKind)>, u32) {
+    let source = std::fs::read_to_string(format!("shaders/{}", shader)).unwrap();
+    let module = naga::front::wgsl::parse_str(&source).unwrap();
+    let layouter = naga::proc::Layouter::new(&module.types, &module.constants);
+
+    let (_, var) = module
+        .global_variables
+        .iter()
+        .find(|(_, var)| var.class == naga::StorageClass::Uniform)
+        .unwrap_or_else(|| panic!("{} has no uniform", shader));
+    let members = match module.types[var.ty].inner {
+        naga::TypeInner::Struct { ref members, .. } => members,
+        ref other => panic!("{}: uniform is not a struct: {:?}", shader, other),
+    };
+
+    let mut layout = Vec::new();
+    let mut offset = 0;
+    let mut struct_align = 1;
+    for member in members {
+        let member_layout = layouter.resolve(member.ty);
+        let align = member_layout.alignment.get();
+        let kind = match module.types[member.ty].inner {
+            naga::TypeInner::Scalar { kind, .. } => kind,
+            ref other => panic!("{}: unexpected member type {:?}", shader, other),
+        };
+        offset = round_up(offset, align);
+        layout.push((offset, kind));
+        offset += member_layout.size;
+        struct_align = struct_align.max(align);
+    }
+    (layout, round_up(round_up(offset, struct_align), 16))
+}
+
+/// Scalar kinds of the members the encoder writes for a section
+fn rust_member_kinds(section: &dyn Reflect) -> Vec<naga::ScalarKind> {
+    let mut kinds = Vec::new();
+    for index in 0..section.fields().len() {
+        match section.get(index) {
+            ParamValue::Float(_) => kinds.push(naga::ScalarKind::Float),
+            ParamValue::Count(_) => kinds.push(naga::ScalarKind::Uint),
+            ParamValue::Color(_) => kinds.extend(&[naga::ScalarKind::Float; 3]),
+        }
+    }
+    kinds
+}
+
+fn check<T: Uniform>(section: &T, shader: &str) {
+    let (expected, expected_size) = wgsl_uniform_layout(shader);
+    let encoder = section.encode();
+    let offsets: Vec<u32> = encoder.offsets().to_vec();
+    let kinds = rust_member_kinds(section);
+
+    assert_eq!(offsets.len(), expected.len(), "{}: member count", shader);
+    for (i, ((offset, kind), (wgsl_offset, wgsl_kind))) in offsets.iter().zip(&kinds).zip(&expected).enumerate() {
+        assert_eq!(offset, wgsl_offset, "{}: offset of member {}", shader, i);
+        assert_eq!(kind, wgsl_kind, "{}: kind of member {}", shader, i);
+    }
+    assert_eq!(encoder.finish().len() as u32, expected_size, "{}: struct size", shader);
+}
+
+fn default_params() -> params::Params {
+    let manager: params::ParamManager =
+        serde_json::from_str(&std::fs::read_to_string("resources/params.json").unwrap()).unwrap();
+    manager.current().clone()
+}
+
+#[test]
+fn particle_params_match_compute_shader() {
+    check(&default_params().particle, "compute.wgsl");
+}
+
+#[test]
+fn decay_params_match_decay_shader() {
+    check(&default_params().decay, "decay.wgsl");
+}
+
+#[test]
+fn diffuse_params_match_diffuse_shader() {
+    check(&default_params().diffuse, "diffuse.wgsl");
+}
+
+#[test]
+fn render_params_match_draw_shader() {
+    check(&default_params().render, "draw.wgsl");
+}
+
+#[test]
+fn vec3_and_arrays_are_padded() {
+    // struct { a: f32; b: vec3<f32>; c: f32; d: array<f32, 2>; e: u32; }
+    let mut encoder = UniformEncoder::new();
+    encoder.f32(1.0);
+    encoder.vec3([2.0, 3.0, 4.0]);
+    encoder.f32(5.0);
+    encoder.f32_array(&[6.0, 7.0]);
+    encoder.u32(8);
+    assert_eq!(encoder.offsets(), &[0, 16, 28, 32, 64]);
+
+    let bytes = encoder.finish();
+    assert_eq!(bytes.len(), 80);
+    assert_eq!(&bytes[28..32], &5.0f32.to_le_bytes());
+    assert_eq!(&bytes[48..52], &7.0f32.to_le_bytes());
+}